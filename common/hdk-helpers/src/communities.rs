@@ -0,0 +1,172 @@
+// Types and lookups of the communities zome that other zomes need in their own validation,
+// where calling the communities zome is not possible, or to read what it returns.
+use hdk::{
+    holochain_core_types::entry::entry_type::AppEntryType,
+    holochain_json_api::{
+        error::JsonError,
+        json::{JsonString, RawString},
+    },
+    prelude::*,
+};
+use holochain_json_derive::DefaultJson;
+use serde_derive::{Deserialize, Serialize};
+
+pub const COMMUNITY_ENTRY_TYPE: &str = "community";
+pub const COMMUNITY_BASE_ENTRY: &str = "community_base";
+// community base -> community. From the shared base for listing, and from the slug for lookups
+pub const COMMUNITY_LINK_TYPE: &str = "member_of";
+// community -> member agent, tagged with their role
+pub const MEMBER_LINK_TYPE: &str = "has_member";
+pub const MODERATOR_GRANT_ENTRY_TYPE: &str = "moderator_grant";
+
+// Committed by a community's creator when making someone a moderator. Its address can be worked
+// out from the community and agent, so validation can check a grant with a single get_entry
+#[derive(Serialize, Deserialize, Debug, Clone, DefaultJson)]
+pub struct ModeratorGrant {
+    pub community: Address,
+    pub agent_id: Address,
+}
+
+pub fn moderator_grant_address(community: &Address, agent_id: &Address) -> ZomeApiResult<Address> {
+    hdk::entry_address(&Entry::App(
+        MODERATOR_GRANT_ENTRY_TYPE.into(),
+        ModeratorGrant {
+            community: community.clone(),
+            agent_id: agent_id.clone(),
+        }
+        .into(),
+    ))
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+    Admin,
+    Moderator,
+    Member,
+}
+
+impl Role {
+    pub fn as_tag(&self) -> &'static str {
+        match self {
+            Role::Admin => "admin",
+            Role::Moderator => "moderator",
+            Role::Member => "member",
+        }
+    }
+
+    pub fn from_tag(tag: &str) -> Option<Role> {
+        match tag {
+            "admin" => Some(Role::Admin),
+            "moderator" => Some(Role::Moderator),
+            "member" => Some(Role::Member),
+            _ => None,
+        }
+    }
+
+    // whether this role has more privileges than another
+    pub fn outranks(&self, other: &Role) -> bool {
+        let rank = |role: &Role| match role {
+            Role::Admin => 2,
+            Role::Moderator => 1,
+            Role::Member => 0,
+        };
+        rank(self) > rank(other)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, DefaultJson)]
+pub struct Member {
+    pub agent_id: Address,
+    pub role: Role,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, DefaultJson)]
+pub struct CommunityStats {
+    pub member_count: usize,
+    pub post_count: usize,
+    pub recent_post_count: usize,
+    pub active_posters: Vec<Address>,
+    pub days: u32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum CommunityStatus {
+    Active,
+    Archived,
+}
+
+impl Default for CommunityStatus {
+    fn default() -> Self {
+        CommunityStatus::Active
+    }
+}
+
+// the part of a community entry needed to tell whether it is archived
+#[derive(Deserialize)]
+struct CommunityState {
+    #[serde(default)]
+    status: CommunityStatus,
+}
+
+// a deleted community has its slug link removed, so this only finds live (or archived) ones
+pub fn community_address_for_slug(slug: &str) -> ZomeApiResult<Option<Address>> {
+    let slug_address = hdk::entry_address(&Entry::App(
+        COMMUNITY_BASE_ENTRY.into(),
+        RawString::from(slug).into(),
+    ))?;
+    Ok(hdk::get_links(
+        &slug_address,
+        LinkMatch::Exactly(COMMUNITY_LINK_TYPE),
+        LinkMatch::Any,
+    )?
+    .addresses()
+    .into_iter()
+    .next())
+}
+
+/**
+ * @brief      The status of the community a post base belongs to
+ *
+ * @param      base        A community slug or address
+ *
+ * @return     None if the base is not a community. Lookup failures are errors so callers fail closed
+ */
+pub fn community_status(base: &str) -> ZomeApiResult<Option<CommunityStatus>> {
+    let address = match community_address_for_slug(base)? {
+        Some(address) => address,
+        None => Address::from(base),
+    };
+    match hdk::get_entry(&address)? {
+        Some(Entry::App(entry_type, content))
+            if entry_type == AppEntryType::from(COMMUNITY_ENTRY_TYPE) =>
+        {
+            let community: CommunityState = serde_json::from_str(&String::from(content))
+                .map_err(|e| ZomeApiError::Internal(e.to_string()))?;
+            Ok(Some(community.status))
+        }
+        _ => Ok(None),
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    #[test]
+    fn test_role_tags() {
+        for role in &[Role::Admin, Role::Moderator, Role::Member] {
+            assert_eq!(Role::from_tag(role.as_tag()).as_ref(), Some(role));
+        }
+        assert_eq!(Role::from_tag("owner"), None);
+    }
+
+    #[test]
+    fn test_outranks() {
+        assert!(Role::Admin.outranks(&Role::Moderator));
+        assert!(Role::Moderator.outranks(&Role::Member));
+        assert!(!Role::Member.outranks(&Role::Admin));
+        assert!(!Role::Moderator.outranks(&Role::Moderator));
+    }
+}
@@ -0,0 +1,310 @@
+// Encryption of text for a set of recipients identified by X25519 public keys.
+//
+// The text is encrypted once with a content key, and the content key is wrapped for each recipient
+// with a key from the Diffie-Hellman of the sender's secret and the recipient's public key.
+// Zomes have no source of randomness so the content key is derived from the sender's secret,
+// the text and a context (e.g. thread and timestamp). The same sender, text and context always
+// give the same ciphertext, which leaks nothing more than that they are the same.
+//
+// Files can be sealed along with a text (see seal_with_files). They are encrypted under the same
+// content key, which is then also derived from the files, so recipients who can open the text can
+// open the files and nobody else can.
+use chacha20poly1305::aead::{generic_array::GenericArray, Aead, NewAead};
+use chacha20poly1305::ChaCha20Poly1305;
+use serde_derive::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use x25519_dalek::{PublicKey, StaticSecret};
+
+pub type Key = [u8; 32];
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SealedKey {
+    // base64 public key of the recipient
+    pub recipient_key: String,
+    // base64 content key encrypted for the recipient
+    pub wrapped_key: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Sealed {
+    // base64 public key of the sender, needed by recipients to unwrap their key
+    pub sender_key: String,
+    // base64 encrypted text
+    pub ciphertext: String,
+    pub recipients: Vec<SealedKey>,
+}
+
+fn hash(parts: &[&[u8]]) -> Key {
+    let mut hasher = Sha256::new();
+    for part in parts {
+        hasher.update(part);
+    }
+    let mut out = [0; 32];
+    out.copy_from_slice(&hasher.finalize());
+    out
+}
+
+// the first 12 bytes of a hash, for the cases where a key is only ever used with one nonce
+fn nonce(parts: &[&[u8]]) -> [u8; 12] {
+    let mut out = [0; 12];
+    out.copy_from_slice(&hash(parts)[..12]);
+    out
+}
+
+fn encrypt(key: &Key, nonce: &[u8; 12], plaintext: &[u8]) -> Result<Vec<u8>, String> {
+    ChaCha20Poly1305::new(GenericArray::from_slice(key))
+        .encrypt(GenericArray::from_slice(nonce), plaintext)
+        .map_err(|_| String::from("Encryption failed"))
+}
+
+fn decrypt(key: &Key, nonce: &[u8; 12], ciphertext: &[u8]) -> Result<Vec<u8>, String> {
+    ChaCha20Poly1305::new(GenericArray::from_slice(key))
+        .decrypt(GenericArray::from_slice(nonce), ciphertext)
+        .map_err(|_| String::from("Decryption failed"))
+}
+
+fn decode_key(encoded: &str) -> Result<Key, String> {
+    let bytes = base64::decode(encoded).map_err(|_| String::from("Invalid key encoding"))?;
+    if bytes.len() != 32 {
+        return Err("Invalid key length".into());
+    }
+    let mut key = [0; 32];
+    key.copy_from_slice(&bytes);
+    Ok(key)
+}
+
+fn wrapping_key(secret: &Key, public: &Key) -> Key {
+    let shared = StaticSecret::from(*secret).diffie_hellman(&PublicKey::from(*public));
+    hash(&[b"wrap", shared.as_bytes()])
+}
+
+// turn any secret seed material (e.g. a signature only the agent can make) into a secret key
+pub fn secret_from_seed(seed: &[u8]) -> Key {
+    hash(&[b"secret", seed])
+}
+
+pub fn public_key(secret: &Key) -> Key {
+    *PublicKey::from(&StaticSecret::from(*secret)).as_bytes()
+}
+
+pub fn encode_key(key: &Key) -> String {
+    base64::encode(key)
+}
+
+pub fn decode_public_key(encoded: &str) -> Result<Key, String> {
+    decode_key(encoded)
+}
+
+// each file sealed with a text gets its own nonce. The text has the all zero one
+fn file_nonce(index: usize) -> [u8; 12] {
+    nonce(&[b"file", &(index as u64).to_le_bytes()])
+}
+
+pub fn seal(
+    sender_secret: &Key,
+    recipients: &[Key],
+    plaintext: &str,
+    context: &[u8],
+) -> Result<Sealed, String> {
+    seal_with_files(sender_secret, recipients, plaintext, &[], context).map(|(sealed, _)| sealed)
+}
+
+// Like seal, also encrypting some files under the content key. Returns the encrypted files in the
+// same order, to be opened with open_file and the index of the file
+pub fn seal_with_files(
+    sender_secret: &Key,
+    recipients: &[Key],
+    plaintext: &str,
+    files: &[&[u8]],
+    context: &[u8],
+) -> Result<(Sealed, Vec<Vec<u8>>), String> {
+    // the files are part of what the key is derived from, so different files never share a key
+    let file_hashes: Vec<Key> = files.iter().map(|file| hash(&[*file])).collect();
+    let mut key_parts: Vec<&[u8]> = vec![
+        &b"content"[..],
+        &sender_secret[..],
+        context,
+        plaintext.as_bytes(),
+    ];
+    key_parts.extend(file_hashes.iter().map(|file_hash| &file_hash[..]));
+    let content_key = hash(&key_parts);
+    // each content key only ever encrypts this one text and these files
+    let ciphertext = encrypt(&content_key, &[0; 12], plaintext.as_bytes())?;
+    let encrypted_files = files
+        .iter()
+        .enumerate()
+        .map(|(index, file)| encrypt(&content_key, &file_nonce(index), file))
+        .collect::<Result<Vec<_>, String>>()?;
+    let recipients = recipients
+        .iter()
+        .map(|recipient| {
+            let wrapped = encrypt(
+                &wrapping_key(sender_secret, recipient),
+                &nonce(&[&ciphertext, recipient]),
+                &content_key,
+            )?;
+            Ok(SealedKey {
+                recipient_key: encode_key(recipient),
+                wrapped_key: base64::encode(&wrapped),
+            })
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+    let sealed = Sealed {
+        sender_key: encode_key(&public_key(sender_secret)),
+        ciphertext: base64::encode(&ciphertext),
+        recipients,
+    };
+    Ok((sealed, encrypted_files))
+}
+
+fn content_key(recipient_secret: &Key, sealed: &Sealed) -> Result<Key, String> {
+    let recipient_key = encode_key(&public_key(recipient_secret));
+    let wrapped = sealed
+        .recipients
+        .iter()
+        .find(|recipient| recipient.recipient_key == recipient_key)
+        .ok_or_else(|| String::from("Not a recipient of this text"))?;
+    let ciphertext =
+        base64::decode(&sealed.ciphertext).map_err(|_| String::from("Invalid ciphertext"))?;
+    let wrapped_key =
+        base64::decode(&wrapped.wrapped_key).map_err(|_| String::from("Invalid wrapped key"))?;
+    let recipient_public = public_key(recipient_secret);
+    let content_key = decrypt(
+        &wrapping_key(recipient_secret, &decode_key(&sealed.sender_key)?),
+        &nonce(&[&ciphertext, &recipient_public]),
+        &wrapped_key,
+    )?;
+    if content_key.len() != 32 {
+        return Err("Invalid content key".into());
+    }
+    let mut key = [0; 32];
+    key.copy_from_slice(&content_key);
+    Ok(key)
+}
+
+pub fn open(recipient_secret: &Key, sealed: &Sealed) -> Result<String, String> {
+    let ciphertext =
+        base64::decode(&sealed.ciphertext).map_err(|_| String::from("Invalid ciphertext"))?;
+    let plaintext = decrypt(&content_key(recipient_secret, sealed)?, &[0; 12], &ciphertext)?;
+    String::from_utf8(plaintext).map_err(|_| String::from("Decrypted text is not UTF-8"))
+}
+
+// Decrypt the file at `index` of those sealed with a text
+pub fn open_file(
+    recipient_secret: &Key,
+    sealed: &Sealed,
+    index: usize,
+    encrypted: &[u8],
+) -> Result<Vec<u8>, String> {
+    decrypt(
+        &content_key(recipient_secret, sealed)?,
+        &file_nonce(index),
+        encrypted,
+    )
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    fn keys(seed: &str) -> (Key, Key) {
+        let secret = secret_from_seed(seed.as_bytes());
+        (secret, public_key(&secret))
+    }
+
+    #[test]
+    fn test_recipients_can_open() {
+        let (alice_secret, alice_public) = keys("alice");
+        let (bob_secret, bob_public) = keys("bob");
+        let sealed = seal(
+            &alice_secret,
+            &[alice_public, bob_public],
+            "hello bob",
+            b"thread1",
+        )
+        .unwrap();
+        assert_eq!(sealed.recipients.len(), 2);
+        assert_eq!(open(&bob_secret, &sealed), Ok("hello bob".to_string()));
+        assert_eq!(open(&alice_secret, &sealed), Ok("hello bob".to_string()));
+    }
+
+    #[test]
+    fn test_others_cannot_open() {
+        let (alice_secret, _) = keys("alice");
+        let (_, bob_public) = keys("bob");
+        let (carol_secret, carol_public) = keys("carol");
+        let mut sealed = seal(&alice_secret, &[bob_public], "secret", b"thread1").unwrap();
+        assert!(open(&carol_secret, &sealed).is_err());
+
+        // pretending to be a recipient doesn't help without the right secret
+        sealed.recipients[0].recipient_key = encode_key(&carol_public);
+        assert!(open(&carol_secret, &sealed).is_err());
+    }
+
+    #[test]
+    fn test_ciphertext_hides_text_and_depends_on_context() {
+        let (alice_secret, alice_public) = keys("alice");
+        let first = seal(&alice_secret, &[alice_public], "same text", b"one").unwrap();
+        let second = seal(&alice_secret, &[alice_public], "same text", b"two").unwrap();
+        assert!(!first.ciphertext.contains("same text"));
+        assert_ne!(first.ciphertext, second.ciphertext);
+        assert_eq!(
+            first,
+            seal(&alice_secret, &[alice_public], "same text", b"one").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_files_are_sealed_with_the_text() {
+        let (alice_secret, alice_public) = keys("alice");
+        let (bob_secret, bob_public) = keys("bob");
+        let (carol_secret, _) = keys("carol");
+        let files: [&[u8]; 2] = [b"first file", b"second file"];
+        let (sealed, encrypted) = seal_with_files(
+            &alice_secret,
+            &[alice_public, bob_public],
+            "two files",
+            &files,
+            b"thread1",
+        )
+        .unwrap();
+        assert_eq!(open(&bob_secret, &sealed), Ok("two files".to_string()));
+        assert_eq!(
+            open_file(&bob_secret, &sealed, 1, &encrypted[1]),
+            Ok(b"second file".to_vec())
+        );
+        // the index is part of the nonce and only recipients can open them
+        assert!(open_file(&bob_secret, &sealed, 0, &encrypted[1]).is_err());
+        assert!(open_file(&carol_secret, &sealed, 0, &encrypted[0]).is_err());
+
+        // different files give a different key even with the same text
+        let (other, _) = seal_with_files(
+            &alice_secret,
+            &[alice_public],
+            "two files",
+            &[b"other file"],
+            b"thread1",
+        )
+        .unwrap();
+        assert_ne!(other.ciphertext, sealed.ciphertext);
+        assert_eq!(
+            seal(&alice_secret, &[alice_public], "two files", b"thread1")
+                .unwrap()
+                .ciphertext,
+            seal_with_files(&alice_secret, &[alice_public], "two files", &[], b"thread1")
+                .unwrap()
+                .0
+                .ciphertext
+        );
+    }
+
+    #[test]
+    fn test_tampering_is_detected() {
+        let (alice_secret, alice_public) = keys("alice");
+        let mut sealed = seal(&alice_secret, &[alice_public], "untouched", b"ctx").unwrap();
+        let mut ciphertext = base64::decode(&sealed.ciphertext).unwrap();
+        ciphertext[0] ^= 1;
+        sealed.ciphertext = base64::encode(&ciphertext);
+        assert!(open(&alice_secret, &sealed).is_err());
+    }
+}
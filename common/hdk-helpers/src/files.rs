@@ -0,0 +1,185 @@
+// Rules for files kept by the files zome. They live here so the zomes that reference files as
+// attachments can check them without calling the files zome from validation.
+use hdk::{
+    holochain_json_api::{error::JsonError, json::JsonString},
+    prelude::*,
+};
+use holochain_json_derive::DefaultJson;
+use serde_derive::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+pub const FILE_CHUNK_ENTRY_TYPE: &str = "file_chunk";
+pub const FILE_MANIFEST_ENTRY_TYPE: &str = "file_manifest";
+
+// bytes of file data in each chunk entry
+pub const MAX_CHUNK_SIZE: usize = 256 * 1024;
+pub const MAX_FILE_SIZE: usize = 10 * 1024 * 1024;
+pub const MAX_NAME_LENGTH: usize = 255;
+// files one message, post or comment can have attached
+pub const MAX_ATTACHMENTS: usize = 10;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, DefaultJson)]
+pub struct FileManifest {
+    pub name: String,
+    pub mime_type: String,
+    // in bytes
+    pub size: usize,
+    // hex sha256 of the whole file
+    pub hash: String,
+    // the chunk entries holding the data, in order
+    pub chunks: Vec<Address>,
+}
+
+pub fn content_hash(data: &[u8]) -> String {
+    Sha256::digest(data)
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+pub fn chunk_count(size: usize) -> usize {
+    (size + MAX_CHUNK_SIZE - 1) / MAX_CHUNK_SIZE
+}
+
+// "type/subtype", optionally with parameters, e.g. "text/plain; charset=utf-8"
+fn is_mime_type(mime_type: &str) -> bool {
+    let essence = mime_type.split(';').next().unwrap_or("").trim();
+    let mut parts = essence.splitn(2, '/');
+    let is_token = |part: &str| {
+        !part.is_empty()
+            && part
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || "!#$&-^_.+".contains(c))
+    };
+    match (parts.next(), parts.next()) {
+        (Some(kind), Some(subtype)) => is_token(kind) && is_token(subtype),
+        _ => false,
+    }
+}
+
+// The name and type of a file. Also used for files whose name and type are kept encrypted
+// elsewhere, e.g. message attachments
+pub fn check_name_and_type(name: &str, mime_type: &str) -> Result<(), String> {
+    if name.trim().is_empty() || name.chars().count() > MAX_NAME_LENGTH {
+        return Err(format!(
+            "File names must be between 1 and {} characters",
+            MAX_NAME_LENGTH
+        ));
+    }
+    if !is_mime_type(mime_type) {
+        return Err("Not a valid MIME type".into());
+    }
+    Ok(())
+}
+
+// Everything about a manifest that can be checked without loading its chunks
+pub fn check_manifest(manifest: &FileManifest) -> Result<(), String> {
+    check_name_and_type(&manifest.name, &manifest.mime_type)?;
+    if manifest.size > MAX_FILE_SIZE {
+        return Err(format!("Files can be at most {} bytes", MAX_FILE_SIZE));
+    }
+    if manifest.chunks.len() != chunk_count(manifest.size) {
+        return Err("Wrong number of chunks for the file size".into());
+    }
+    if manifest.hash.len() != 64
+        || !manifest
+            .hash
+            .chars()
+            .all(|c| c.is_ascii_digit() || ('a'..='f').contains(&c))
+    {
+        return Err("File hashes must be hex encoded sha256".into());
+    }
+    Ok(())
+}
+
+// There is a limit on how many files one entry can have attached, each at most once.
+// Checked from the entry alone so every validator gives the same answer
+pub fn validate_attachments(attachments: &[Address]) -> Result<(), String> {
+    if attachments.len() > MAX_ATTACHMENTS {
+        return Err(format!("At most {} files can be attached", MAX_ATTACHMENTS));
+    }
+    if attachments
+        .iter()
+        .enumerate()
+        .any(|(i, attachment)| attachments[..i].contains(attachment))
+    {
+        return Err("The same file cannot be attached twice".into());
+    }
+    Ok(())
+}
+
+// Attachments must be stored files. This looks them up, so it is for checking before committing
+// rather than from validation
+pub fn check_attachments(attachments: &[Address]) -> ZomeApiResult<()> {
+    validate_attachments(attachments).map_err(ZomeApiError::Internal)?;
+    for attachment in attachments {
+        match hdk::get_entry(attachment)? {
+            Some(Entry::App(entry_type, _))
+                if String::from(entry_type) == FILE_MANIFEST_ENTRY_TYPE => {}
+            _ => {
+                return Err(ZomeApiError::Internal(format!(
+                    "Attachment {} is not a stored file",
+                    attachment
+                )))
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    fn manifest(size: usize) -> FileManifest {
+        FileManifest {
+            name: "notes.txt".into(),
+            mime_type: "text/plain; charset=utf-8".into(),
+            size,
+            hash: content_hash(b"notes"),
+            chunks: (0..chunk_count(size))
+                .map(|i| Address::from(format!("chunk{}", i)))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_chunk_count() {
+        assert_eq!(chunk_count(0), 0);
+        assert_eq!(chunk_count(1), 1);
+        assert_eq!(chunk_count(MAX_CHUNK_SIZE), 1);
+        assert_eq!(chunk_count(MAX_CHUNK_SIZE + 1), 2);
+    }
+
+    #[test]
+    fn test_check_manifest() {
+        assert_eq!(check_manifest(&manifest(MAX_CHUNK_SIZE * 3)), Ok(()));
+        assert!(check_manifest(&manifest(MAX_FILE_SIZE + 1)).is_err());
+        assert!(check_manifest(&FileManifest {
+            mime_type: "text".into(),
+            ..manifest(10)
+        })
+        .is_err());
+        assert!(check_manifest(&FileManifest {
+            chunks: vec![],
+            ..manifest(10)
+        })
+        .is_err());
+        assert!(check_manifest(&FileManifest {
+            hash: "not a hash".into(),
+            ..manifest(10)
+        })
+        .is_err());
+    }
+
+    #[test]
+    fn test_validate_attachments() {
+        let file = |i: usize| Address::from(format!("file{}", i));
+        assert_eq!(validate_attachments(&[file(0), file(1)]), Ok(()));
+        assert!(validate_attachments(&[file(0), file(0)]).is_err());
+        assert!(
+            validate_attachments(&(0..=MAX_ATTACHMENTS).map(file).collect::<Vec<Address>>())
+                .is_err()
+        );
+    }
+}
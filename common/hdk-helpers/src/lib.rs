@@ -1,4 +1,12 @@
+pub mod communities;
+pub mod crypto;
+pub mod files;
+pub mod posts;
+
 use hdk::prelude::*;
+use serde::de::DeserializeOwned;
+use serde_derive::Deserialize;
+use serde_json::json;
 use std::collections::HashSet;
 
 pub fn commit_if_not_in_chain(entry: &Entry) -> ZomeApiResult<Address> {
@@ -13,6 +21,189 @@ pub fn commit_if_not_in_chain(entry: &Entry) -> ZomeApiResult<Address> {
     }
 }
 
+// call a public function of another zome in this instance and unpack the ZomeApiResult it returns.
+// An Err on the other side comes back as a ZomeApiError::Internal holding the serialized error
+pub fn call_zome<T: DeserializeOwned>(
+    zome_name: &str,
+    fn_name: &str,
+    params: serde_json::Value,
+) -> ZomeApiResult<T> {
+    let response = hdk::call(
+        hdk::THIS_INSTANCE,
+        zome_name,
+        Address::from(hdk::PUBLIC_TOKEN.to_string()),
+        fn_name,
+        JsonString::from_json(&params.to_string()),
+    )?;
+    let mut result: serde_json::Value = serde_json::from_str(&String::from(response))
+        .map_err(|e| ZomeApiError::Internal(e.to_string()))?;
+    match result.get_mut("Ok").map(serde_json::Value::take) {
+        Some(ok) => serde_json::from_value(ok).map_err(|e| ZomeApiError::Internal(e.to_string())),
+        None => Err(ZomeApiError::Internal(
+            result
+                .get("Err")
+                .map(|err| err.to_string())
+                .unwrap_or_else(|| result.to_string()),
+        )),
+    }
+}
+
+// links from a mentioned agent to the post, comment or message mentioning them.
+// Tagged "<kind>:<timestamp>" so an agent's mentions can be listed without loading the content
+pub const MENTION_LINK_TYPE: &str = "mentioned_in";
+
+fn is_mention_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '-' || c == '.'
+}
+
+// find the distinct names following an @ in some text. The @ must not follow a word character,
+// so email addresses are not mentions, and trailing dots (end of a sentence) are not part of the name
+pub fn extract_mentions(text: &str) -> Vec<String> {
+    let mut mentions = Vec::new();
+    let mut previous = None;
+    let mut chars = text.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        if c == '@' && !previous.map_or(false, is_mention_char) {
+            let start = i + 1;
+            let mut end = start;
+            while let Some((j, next)) = chars.peek().cloned() {
+                if !is_mention_char(next) {
+                    break;
+                }
+                end = j + next.len_utf8();
+                chars.next();
+            }
+            let name = text[start..end].trim_end_matches('.').to_string();
+            if !name.is_empty() && !mentions.contains(&name) {
+                mentions.push(name);
+            }
+            previous = text[..end].chars().last();
+        } else {
+            previous = Some(c);
+        }
+    }
+    mentions
+}
+
+pub fn mention_link_tag(kind: &str, timestamp: &str) -> String {
+    format!("{}:{}", kind, timestamp)
+}
+
+// Resolve the mentions in some text to registered agents using the people zome and link each of them
+// to the content. This is best effort: the content is already committed, so a failed lookup or link
+// only loses the mention. Returns the agents that were linked
+pub fn link_mentions(text: &str, target: &Address, kind: &str, timestamp: &str) -> Vec<Address> {
+    link_mentioned_names(extract_mentions(text), target, kind, timestamp)
+}
+
+// Like link_mentions for the new text of an edit, linking only the names the previous text didn't mention
+pub fn link_new_mentions(
+    previous_text: &str,
+    text: &str,
+    target: &Address,
+    kind: &str,
+    timestamp: &str,
+) -> Vec<Address> {
+    let previous = extract_mentions(previous_text);
+    let names = extract_mentions(text)
+        .into_iter()
+        .filter(|name| !previous.contains(name))
+        .collect();
+    link_mentioned_names(names, target, kind, timestamp)
+}
+
+fn link_mentioned_names(
+    names: Vec<String>,
+    target: &Address,
+    kind: &str,
+    timestamp: &str,
+) -> Vec<Address> {
+    if names.is_empty() {
+        return Vec::new();
+    }
+    let agents: Vec<Address> =
+        call_zome("people", "resolve_mentions", json!({ "names": names })).unwrap_or_default();
+    agents
+        .into_iter()
+        .filter(|agent| {
+            hdk::link_entries(
+                agent,
+                target,
+                MENTION_LINK_TYPE,
+                &mention_link_tag(kind, timestamp),
+            )
+            .is_ok()
+        })
+        .collect()
+}
+
+// the fields of a post, comment or message that mention links are checked against
+#[derive(Deserialize)]
+struct MentioningContent {
+    creator: Address,
+}
+
+/**
+ * @brief      Validation for mention links, shared by every zome whose content can mention agents
+ *
+ * @return     Ok if the link is added by the creator of the content it points to and tagged with the
+ *             content's entry type, or removed by that creator or the mentioned agent
+ */
+pub fn validate_mention_link(validation_data: hdk::LinkValidationData) -> Result<(), String> {
+    let (link, sources, adding) = match validation_data {
+        hdk::LinkValidationData::LinkAdd {
+            link,
+            validation_data,
+        } => (link, validation_data.sources(), true),
+        hdk::LinkValidationData::LinkRemove {
+            link,
+            validation_data,
+        } => (link, validation_data.sources(), false),
+    };
+    let link = link.link();
+    let (kind, content) = match hdk::get_entry(link.target()) {
+        Ok(Some(Entry::App(entry_type, content))) => (String::from(entry_type), content),
+        _ => return Err("Mentions must point to a post, comment or message".into()),
+    };
+    let content: MentioningContent = serde_json::from_str(&String::from(content))
+        .map_err(|_| String::from("Mentions must point to a post, comment or message"))?;
+    if adding {
+        if !sources.contains(&content.creator) {
+            return Err("Only the creator of some content can link the agents it mentions".into());
+        }
+        if !link.tag().starts_with(&mention_link_tag(&kind, "")) {
+            return Err("Mention links must be tagged with the kind of content".into());
+        }
+        Ok(())
+    } else if sources.contains(&content.creator) || sources.contains(link.base()) {
+        Ok(())
+    } else {
+        Err("Only the creator of the content or the mentioned agent can remove a mention".into())
+    }
+}
+
+// an absolute http(s) URL with a host and no whitespace. Enough to keep profile links clickable
+pub fn is_valid_url(url: &str) -> bool {
+    let rest = if url.starts_with("https://") {
+        &url["https://".len()..]
+    } else if url.starts_with("http://") {
+        &url["http://".len()..]
+    } else {
+        return false;
+    };
+    let host = rest
+        .split(|c| c == '/' || c == '?' || c == '#')
+        .next()
+        .unwrap_or("");
+    !host.is_empty()
+        && !host.starts_with('.')
+        && !host.ends_with('.')
+        && host
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '.' || c == '-' || c == ':')
+        && !url.chars().any(char::is_whitespace)
+}
+
 pub trait DagList<E: Into<JsonString> + Clone> {
     fn author(
         &mut self,
@@ -229,6 +420,28 @@ pub mod tests {
 
     impl DagListDebug<i32> for TestStore {}
 
+    #[test]
+    fn test_extract_mentions() {
+        assert_eq!(
+            extract_mentions("hey @alice and @bob_smith, see @alice."),
+            vec!["alice".to_string(), "bob_smith".to_string()]
+        );
+        assert_eq!(extract_mentions("mail me at me@example.com"), Vec::<String>::new());
+        assert_eq!(extract_mentions("@ nobody and @@double"), vec!["double".to_string()]);
+        assert_eq!(extract_mentions("(@zoë)"), vec!["zoë".to_string()]);
+    }
+
+    #[test]
+    fn test_is_valid_url() {
+        assert!(is_valid_url("https://hylo.com"));
+        assert!(is_valid_url("http://localhost:3000/u/alice?tab=posts"));
+        assert!(!is_valid_url("hylo.com"));
+        assert!(!is_valid_url("javascript:alert(1)"));
+        assert!(!is_valid_url("https://"));
+        assert!(!is_valid_url("https://hylo .com"));
+        assert!(!is_valid_url("https://hylo.com/a b"));
+    }
+
     #[test]
     fn test_get_nothing() {
         let mut store = TestStore::new();
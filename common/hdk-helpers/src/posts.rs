@@ -0,0 +1,10 @@
+// Types of the posts zome that the comments zome reads in its own validation
+use serde_derive::Deserialize;
+
+pub const POST_ENTRY_TYPE: &str = "post";
+
+// the part of a post needed to find the community it was made in
+#[derive(Deserialize, Debug, Clone)]
+pub struct PostBase {
+    pub base: String,
+}
@@ -36,8 +36,13 @@ require('./single_agent/comments')(orchestrator.registerScenario)
 require('./single_agent/threads')(orchestrator.registerScenario)
 require('./single_agent/messages')(orchestrator.registerScenario)
 require('./single_agent/people')(orchestrator.registerScenario)
+require('./single_agent/files')(orchestrator.registerScenario)
 
 require('./multi_agent/posts')(orchestrator.registerScenario)
+require('./multi_agent/comments')(orchestrator.registerScenario)
+require('./multi_agent/mentions')(orchestrator.registerScenario)
+require('./multi_agent/threads')(orchestrator.registerScenario)
+require('./multi_agent/people')(orchestrator.registerScenario)
 
 orchestrator.run().then(stats => {
   console.log("All done.")
@@ -0,0 +1,63 @@
+const { one } = require('../config')
+
+module.exports = (scenario) => {
+
+scenario('Only the creator or a community moderator can delete a comment', async (s, t) => {
+    const { alice, bob, carol } = await s.players({alice: one, bob: one, carol: one}, true)
+
+    const slug = "moderated"
+    const community = await alice.callSync("app", "communities", "create", { name: "Moderated", slug })
+    const post = await alice.callSync("app", "posts", "create", {
+      base: slug,
+      title: "a post",
+      details: "",
+      post_type: "",
+      announcement: false,
+      timestamp: ""
+    })
+    await s.consistency()
+
+    const comment = (agent, text) => agent.callSync("app", "comments", "create", {
+      base: post.Ok.address,
+      text,
+      timestamp: "2019-03-29T01:58:10+00:00"
+    })
+    const bobComment = await comment(bob, "spam")
+    await s.consistency()
+    const reply = await alice.callSync("app", "comments", "create", {
+      base: post.Ok.address,
+      text: "please don't",
+      timestamp: "2019-03-29T01:58:11+00:00",
+      parent: bobComment.Ok.address
+    })
+    await s.consistency()
+
+    // carol can neither edit nor delete bob's comment
+    const editResult = await carol.callSync("app", "comments", "update", {
+      address: bobComment.Ok.address,
+      text: "hacked",
+      timestamp: "2019-03-29T01:58:10+00:00"
+    })
+    t.ok(editResult.Err, "Only the creator can edit")
+    const carolDelete = await carol.callSync("app", "comments", "delete", { address: bobComment.Ok.address })
+    t.ok(carolDelete.Err, "Non moderators cannot delete")
+
+    // once alice makes carol a moderator she can remove it
+    await alice.callSync("app", "communities", "add_moderator", {
+      community_address: community.Ok.address,
+      agent_id: carol.info('app').agentAddress
+    })
+    await s.consistency()
+    const moderatorDelete = await carol.callSync("app", "comments", "delete", { address: bobComment.Ok.address })
+    t.ok(moderatorDelete.Ok, "Moderator can delete")
+    await s.consistency()
+
+    const allResult = await alice.callSync("app", "comments", "all_for_base", { base: post.Ok.address })
+    t.deepEqual(allResult.Ok.comments.map(c => c.address), [reply.Ok.address])
+
+    // the reply is still under the removed comment
+    const thread = await alice.callSync("app", "comments", "thread_for_base", { base: post.Ok.address, depth: 1 })
+    t.equal(thread.Ok.comments[0].removed.by, carol.info('app').agentAddress)
+    t.deepEqual(thread.Ok.comments[0].replies.comments.map(c => c.address), [reply.Ok.address])
+  })
+}
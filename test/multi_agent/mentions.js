@@ -0,0 +1,95 @@
+const { one } = require('../config')
+
+module.exports = (scenario) => {
+
+scenario('Mentions in posts, comments and messages show up in the mentioned agent\'s inbox', async (s, t) => {
+    const { alice, bob } = await s.players({alice: one, bob: one}, true)
+
+    await bob.callSync("app", "people", "register_user", { name: "Bob Smith", avatar_url: "" })
+    await s.consistency()
+
+    const post = await alice.callSync("app", "posts", "create", {
+      base: "mentions",
+      title: "hello",
+      details: "what do you think @BobSmith?",
+      post_type: "",
+      announcement: false,
+      timestamp: "2019-03-29T01:00:00+00:00"
+    })
+    const comment = await alice.callSync("app", "comments", "create", {
+      base: post.Ok.address,
+      text: "@bobsmith ping, and @nobody",
+      timestamp: "2019-03-29T02:00:00+00:00"
+    })
+    const thread = await alice.callSync("app", "messages", "create_thread", { participant_ids: [bob.info('app').agentAddress] })
+    const message = await alice.callSync("app", "messages", "create", {
+      thread_address: thread.Ok,
+      text: "hi @bobsmith",
+      timestamp: "2019-03-29T03:00:00+00:00"
+    })
+    await s.consistency()
+
+    const mentions = await bob.callSync("app", "people", "my_mentions", {})
+    t.deepEqual(mentions.Ok, [
+      { address: message.Ok.address, kind: "message", timestamp: "2019-03-29T03:00:00+00:00" },
+      { address: comment.Ok.address, kind: "comment", timestamp: "2019-03-29T02:00:00+00:00" },
+      { address: post.Ok.address, kind: "post", timestamp: "2019-03-29T01:00:00+00:00" }
+    ])
+
+    const recent = await bob.callSync("app", "people", "my_mentions", { since: "2019-03-29T01:30:00+00:00", limit: 1 })
+    t.deepEqual(recent.Ok.map(m => m.kind), ["message"])
+
+    // the same time written with another offset filters the same way
+    const offset = await bob.callSync("app", "people", "my_mentions", { since: "2019-03-29T02:30:00+01:00" })
+    t.deepEqual(offset.Ok.map(m => m.kind), ["message", "comment"])
+
+    // a mention added by an edit shows up from when the edit was made
+    const later = await alice.callSync("app", "messages", "create", {
+      thread_address: thread.Ok,
+      text: "see you tomorrow",
+      timestamp: "2019-03-29T04:00:00+00:00"
+    })
+    await alice.callSync("app", "messages", "edit", {
+      message_addr: later.Ok.address,
+      text: "see you tomorrow @bobsmith",
+      timestamp: "2019-03-29T05:00:00+00:00"
+    })
+    await s.consistency()
+    const afterEdit = await bob.callSync("app", "people", "my_mentions", { limit: 1 })
+    t.deepEqual(afterEdit.Ok, [
+      { address: later.Ok.address, kind: "message", timestamp: "2019-03-29T05:00:00+00:00" }
+    ])
+
+    const aliceMentions = await alice.callSync("app", "people", "my_mentions", {})
+    t.deepEqual(aliceMentions.Ok, [])
+
+    // comment edits link new mentions too
+    const quiet = await alice.callSync("app", "comments", "create", {
+      base: post.Ok.address,
+      text: "no one here",
+      timestamp: "2019-03-29T06:00:00+00:00"
+    })
+    await alice.callSync("app", "comments", "update", {
+      address: quiet.Ok.address,
+      text: "no one here but @bobsmith",
+      timestamp: "2019-03-29T07:00:00+00:00"
+    })
+    await s.consistency()
+    const afterCommentEdit = await bob.callSync("app", "people", "my_mentions", { limit: 1 })
+    t.deepEqual(afterCommentEdit.Ok, [
+      { address: quiet.Ok.address, kind: "comment", timestamp: "2019-03-29T07:00:00+00:00" }
+    ])
+
+    // a name that more than one agent has mentions nobody
+    await alice.callSync("app", "people", "register_user", { name: "Bob Smith", avatar_url: "" })
+    await s.consistency()
+    await alice.callSync("app", "comments", "create", {
+      base: post.Ok.address,
+      text: "which @bobsmith?",
+      timestamp: "2019-03-29T08:00:00+00:00"
+    })
+    await s.consistency()
+    const ambiguous = await bob.callSync("app", "people", "my_mentions", { limit: 1 })
+    t.deepEqual(ambiguous.Ok.map(m => m.address), [quiet.Ok.address])
+  })
+}
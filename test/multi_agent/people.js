@@ -0,0 +1,66 @@
+const { one } = require('../config')
+
+module.exports = (scenario) => {
+
+scenario('List the members of a community with their profiles', async (s, t) => {
+    const { alice, bob, carol, dave } = await s.players({alice: one, bob: one, carol: one, dave: one}, true)
+    const [aliceAddress, bobAddress, carolAddress, daveAddress] = [alice, bob, carol, dave].map(agent => agent.info('app').agentAddress)
+
+    await alice.callSync("app", "people", "register_user", { name: "Alice", avatar_url: "" })
+    await bob.callSync("app", "people", "register_user", { name: "Bob", avatar_url: "" })
+    await carol.callSync("app", "people", "register_user", { name: "Carol", avatar_url: "" })
+    const slug = "gardeners"
+    const community = await alice.callSync("app", "communities", "create", { name: "Gardeners", slug })
+    await s.consistency()
+
+    await bob.callSync("app", "communities", "join", { community_address: community.Ok.address })
+    await carol.callSync("app", "communities", "join", { community_address: community.Ok.address })
+    // dave never registers a profile
+    await dave.callSync("app", "communities", "join", { community_address: community.Ok.address })
+    await alice.callSync("app", "communities", "add_moderator", {
+      community_address: community.Ok.address,
+      agent_id: carolAddress
+    })
+    await bob.callSync("app", "posts", "create", {
+      base: slug,
+      title: "seed swap",
+      details: "",
+      post_type: "",
+      announcement: false,
+      timestamp: "2019-06-28T00:00:00+00:00"
+    })
+    await s.consistency()
+
+    // carol's plain membership link and moderator link give one row with her highest role
+    const listed = (await alice.callSync("app", "communities", "members", { community_address: community.Ok.address })).Ok
+    t.deepEqual(listed.filter(m => m.agent_id === carolAddress), [{ agent_id: carolAddress, role: "moderator" }])
+    t.equal(listed.length, 4)
+
+    const members = (params) => alice.callSync("app", "people", "community_members", { community_address: community.Ok.address, ...params })
+
+    // in agent address order, with members who have no profile listed without one
+    const expected = [
+      { agent_id: aliceAddress, role: "admin", name: "Alice" },
+      { agent_id: bobAddress, role: "member", name: "Bob" },
+      { agent_id: carolAddress, role: "moderator", name: "Carol" },
+      { agent_id: daveAddress, role: "member", name: undefined }
+    ].sort((a, b) => a.agent_id < b.agent_id ? -1 : 1)
+    const all = await members({})
+    t.deepEqual(all.Ok.members.map(m => ({ agent_id: m.agent_id, role: m.role, name: m.name })), expected)
+    t.equal(all.Ok.members.find(m => m.agent_id === aliceAddress).address, aliceAddress)
+    t.equal(all.Ok.members.find(m => m.agent_id === daveAddress).address, undefined)
+
+    const firstPage = await members({ limit: 3 })
+    t.equal(firstPage.Ok.more, true)
+    const secondPage = await members({ limit: 3, after: firstPage.Ok.members[2].agent_id })
+    t.deepEqual(secondPage.Ok.members.map(m => m.agent_id), [expected[3].agent_id])
+    t.equal(secondPage.Ok.more, false)
+
+    t.deepEqual((await members({ role: "moderator" })).Ok.members.map(m => m.name), ["Carol"])
+    t.deepEqual((await members({ role: "member" })).Ok.members.map(m => m.agent_id), expected.filter(m => m.role === "member").map(m => m.agent_id))
+
+    const active = await members({ active_days: 7, now: "2019-07-01T00:00:00+00:00" })
+    t.deepEqual(active.Ok.members.map(m => m.agent_id), [bobAddress])
+    t.ok((await members({ active_days: 7 })).Err, "now is needed with active_days")
+  })
+}
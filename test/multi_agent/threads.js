@@ -0,0 +1,209 @@
+const { one } = require('../config')
+
+module.exports = (scenario) => {
+
+scenario('Get summaries of all threads sorted by last activity', async (s, t) => {
+    const { alice, bob, carol } = await s.players({alice: one, bob: one, carol: one}, true)
+    const aliceAddress = alice.info('app').agentAddress
+    const bobAddress = bob.info('app').agentAddress
+    const carolAddress = carol.info('app').agentAddress
+    await bob.callSync("app", "people", "register_user", { name: "Bob", avatar_url: "bob.png" })
+
+    const withBob = (await alice.callSync("app", "messages", "find_or_create_thread", { participant_ids: [bobAddress] })).Ok
+    const withCarol = (await alice.callSync("app", "messages", "find_or_create_thread", { participant_ids: [carolAddress] })).Ok
+    const group = (await alice.callSync("app", "messages", "find_or_create_thread", { participant_ids: [bobAddress, carolAddress] })).Ok
+    await alice.callSync("app", "messages", "create", { thread_address: group, text: "hi all", timestamp: "2019-03-29T01:00:00+00:00" })
+    await s.consistency()
+    await bob.callSync("app", "messages", "create", { thread_address: withBob, text: "hi alice", timestamp: "2019-03-29T02:00:00+00:00" })
+    const latest = await bob.callSync("app", "messages", "create", { thread_address: withBob, text: "you there?", timestamp: "2019-03-29T03:00:00+00:00" })
+    await s.consistency()
+
+    const summaries = (await alice.callSync("app", "messages", "get_thread_summaries", {})).Ok
+    t.deepEqual(summaries.map(summary => summary.address), [withBob, group, withCarol])
+
+    t.deepEqual(summaries[0].latest_message, latest.Ok)
+    t.equal(summaries[0].unread_count, 2)
+    const bobParticipant = summaries[0].participants.find(p => p.address === bobAddress)
+    t.deepEqual(bobParticipant, { address: bobAddress, name: "Bob", avatar_url: "bob.png" })
+    const aliceParticipant = summaries[0].participants.find(p => p.address === aliceAddress)
+    t.deepEqual(aliceParticipant, { address: aliceAddress, name: "", avatar_url: "" })
+
+    t.equal(summaries[1].unread_count, 0)
+    t.equal(summaries[2].latest_message, null)
+  })
+
+scenario('Mark messages as read and see who has read what', async (s, t) => {
+    const { alice, bob } = await s.players({alice: one, bob: one}, true)
+    const bobAddress = bob.info('app').agentAddress
+
+    const thread = (await alice.callSync("app", "messages", "find_or_create_thread", { participant_ids: [bobAddress] })).Ok
+    const first = (await alice.callSync("app", "messages", "create", { thread_address: thread, text: "one", timestamp: "2019-03-29T01:00:00+00:00" })).Ok
+    const second = (await alice.callSync("app", "messages", "create", { thread_address: thread, text: "two", timestamp: "2019-03-29T02:00:00+00:00" })).Ok
+    await s.consistency()
+
+    t.equal((await bob.callSync("app", "messages", "get_unread_count", { thread_address: thread })).Ok, 2)
+
+    await bob.callSync("app", "messages", "mark_read", { thread_address: thread, up_to_message: first.address, share: true })
+    await s.consistency()
+    t.equal((await bob.callSync("app", "messages", "get_unread_count", { thread_address: thread })).Ok, 1)
+    t.deepEqual((await alice.callSync("app", "messages", "get_read_positions", { thread_address: thread })).Ok, [
+      { agent_id: bobAddress, message_address: first.address }
+    ])
+
+    // marking privately, the default, moves the unread count but not the shared position
+    await bob.callSync("app", "messages", "mark_read", { thread_address: thread, up_to_message: second.address })
+    await s.consistency()
+    t.equal((await bob.callSync("app", "messages", "get_unread_count", { thread_address: thread })).Ok, 0)
+    t.deepEqual((await alice.callSync("app", "messages", "get_read_positions", { thread_address: thread })).Ok, [
+      { agent_id: bobAddress, message_address: first.address }
+    ])
+  })
+
+scenario('Only participants can post in a thread', async (s, t) => {
+    const { alice, bob, carol } = await s.players({alice: one, bob: one, carol: one}, true)
+
+    const thread = (await alice.callSync("app", "messages", "find_or_create_thread", { participant_ids: [bob.info('app').agentAddress] })).Ok
+    await s.consistency()
+
+    const bobResult = await bob.callSync("app", "messages", "create", { thread_address: thread, text: "hi", timestamp: "2019-03-29T01:00:00+00:00" })
+    t.ok(bobResult.Ok, "Participant can post")
+    const carolResult = await carol.callSync("app", "messages", "create", { thread_address: thread, text: "intruder", timestamp: "2019-03-29T01:00:00+00:00" })
+    t.ok(carolResult.Err, "Non participant cannot post")
+  })
+
+scenario('Only thread participants can read message text', async (s, t) => {
+    const { alice, bob, carol } = await s.players({alice: one, bob: one, carol: one}, true)
+    const bobAddress = bob.info('app').agentAddress
+    // everyone publishes their key when they start. Wait for bob's to reach alice
+    await s.consistency()
+
+    const thread = (await alice.callSync("app", "messages", "find_or_create_thread", { participant_ids: [bobAddress] })).Ok
+    const sent = (await alice.callSync("app", "messages", "create", { thread_address: thread, text: "just for bob", timestamp: "2019-03-29T01:00:00+00:00" })).Ok
+    await s.consistency()
+
+    t.equal((await alice.callSync("app", "messages", "get", { message_addr: sent.address })).Ok.text, "just for bob")
+    t.equal((await bob.callSync("app", "messages", "get", { message_addr: sent.address })).Ok.text, "just for bob")
+    t.ok((await carol.callSync("app", "messages", "get", { message_addr: sent.address })).Err)
+    t.deepEqual((await carol.callSync("app", "messages", "get_thread_messages", { thread_address: thread })).Ok, [])
+
+    // joining later doesn't open up earlier messages, even once they are edited
+    await alice.callSync("app", "messages", "add_participants", { thread_address: thread, participant_ids: [carol.info('app').agentAddress] })
+    await alice.callSync("app", "messages", "edit", { message_addr: sent.address, text: "still just for bob", timestamp: "2019-03-29T02:00:00+00:00" })
+    await s.consistency()
+    t.equal((await bob.callSync("app", "messages", "get", { message_addr: sent.address })).Ok.text, "still just for bob")
+    t.ok((await carol.callSync("app", "messages", "get", { message_addr: sent.address })).Err)
+  })
+
+scenario('Messages are delivered directly and only listed once', async (s, t) => {
+    const { alice, bob } = await s.players({alice: one, bob: one}, true)
+    const bobAddress = bob.info('app').agentAddress
+
+    const thread = (await alice.callSync("app", "messages", "find_or_create_thread", { participant_ids: [bobAddress] })).Ok
+    await s.consistency()
+
+    const sent = (await alice.callSync("app", "messages", "create", { thread_address: thread, text: "straight to you", timestamp: "2019-03-29T01:00:00+00:00" })).Ok
+    // no waiting for the DHT
+    const delivered = (await bob.callSync("app", "messages", "get_thread_messages", { thread_address: thread })).Ok
+    t.deepEqual(delivered, [sent])
+
+    await s.consistency()
+    const afterGossip = (await bob.callSync("app", "messages", "get_thread_messages", { thread_address: thread })).Ok
+    t.deepEqual(afterGossip, [sent])
+  })
+
+scenario('Add participants to and leave a group thread', async (s, t) => {
+    const { alice, bob, carol } = await s.players({alice: one, bob: one, carol: one}, true)
+    const aliceAddress = alice.info('app').agentAddress
+    const bobAddress = bob.info('app').agentAddress
+    const carolAddress = carol.info('app').agentAddress
+
+    const thread = (await alice.callSync("app", "messages", "find_or_create_thread", { participant_ids: [bobAddress] })).Ok
+    await s.consistency()
+    const added = await bob.callSync("app", "messages", "add_participants", { thread_address: thread, participant_ids: [carolAddress] })
+    t.deepEqual(added.Ok.sort(), [aliceAddress, bobAddress, carolAddress].sort())
+    await s.consistency()
+    t.deepEqual((await carol.callSync("app", "messages", "get_threads", {})).Ok, [thread])
+    t.ok((await carol.callSync("app", "messages", "create", { thread_address: thread, text: "thanks for adding me", timestamp: "2019-03-29T01:00:00+00:00" })).Ok)
+
+    // with carol in it the thread isn't just alice and bob any more, so a direct message goes elsewhere
+    const direct = (await alice.callSync("app", "messages", "find_or_create_thread", { participant_ids: [bobAddress] })).Ok
+    t.notEqual(direct, thread)
+    t.deepEqual((await alice.callSync("app", "messages", "get_thread_participants", { thread_address: direct })).Ok.sort(), [aliceAddress, bobAddress].sort())
+
+    const left = await carol.callSync("app", "messages", "leave_thread", { thread_address: thread })
+    t.deepEqual(left.Ok.sort(), [aliceAddress, bobAddress].sort())
+    await s.consistency()
+    t.deepEqual((await carol.callSync("app", "messages", "get_threads", {})).Ok, [])
+    t.ok((await carol.callSync("app", "messages", "create", { thread_address: thread, text: "still here?", timestamp: "2019-03-29T02:00:00+00:00" })).Err)
+
+    await alice.callSync("app", "messages", "create", { thread_address: thread, text: "after carol left", timestamp: "2019-03-29T03:00:00+00:00" })
+    await s.consistency()
+    const bobSees = (await bob.callSync("app", "messages", "get_thread_messages", { thread_address: thread })).Ok
+    t.deepEqual(bobSees.map(message => message.text).sort(), ["after carol left", "thanks for adding me"])
+    const carolSees = (await carol.callSync("app", "messages", "get_thread_messages", { thread_address: thread })).Ok
+    t.deepEqual(carolSees.map(message => message.text), ["thanks for adding me"])
+  })
+
+scenario('Only participants can read message attachments', async (s, t) => {
+    const { alice, bob, carol } = await s.players({alice: one, bob: one, carol: one}, true)
+    const bobAddress = bob.info('app').agentAddress
+
+    const thread = (await alice.callSync("app", "messages", "find_or_create_thread", { participant_ids: [bobAddress] })).Ok
+    await s.consistency()
+    const data = Buffer.from("just for bob").toString('base64')
+    const message = (await alice.callSync("app", "messages", "create", {
+      thread_address: thread, text: "attached", timestamp: "2019-03-29T01:00:00+00:00",
+      attachments: [{ name: "note.txt", mime_type: "text/plain", data }]
+    })).Ok
+    await s.consistency()
+
+    const attachment = { message_addr: message.address, attachment: message.attachments[0].address }
+    t.equal((await bob.callSync("app", "messages", "get_attachment", attachment)).Ok.data, data)
+    t.ok((await carol.callSync("app", "messages", "get_attachment", attachment)).Err)
+  })
+
+scenario('Leave a direct thread and start it again', async (s, t) => {
+    const { alice, bob } = await s.players({alice: one, bob: one}, true)
+    const aliceAddress = alice.info('app').agentAddress
+    const bobAddress = bob.info('app').agentAddress
+
+    const thread = (await alice.callSync("app", "messages", "find_or_create_thread", { participant_ids: [bobAddress] })).Ok
+    await s.consistency()
+    await alice.callSync("app", "messages", "leave_thread", { thread_address: thread })
+    await s.consistency()
+
+    // alice can't add herself back to the thread she left, so she gets a new one with bob
+    const restarted = await alice.callSync("app", "messages", "find_or_create_thread", { participant_ids: [bobAddress] })
+    t.ok(restarted.Ok)
+    t.notEqual(restarted.Ok, thread)
+    t.deepEqual((await alice.callSync("app", "messages", "get_thread_participants", { thread_address: restarted.Ok })).Ok.sort(), [aliceAddress, bobAddress].sort())
+    await s.consistency()
+    t.deepEqual((await alice.callSync("app", "messages", "find_or_create_thread", { participant_ids: [bobAddress] })).Ok, restarted.Ok)
+
+    // leaving that one too starts yet another
+    await alice.callSync("app", "messages", "leave_thread", { thread_address: restarted.Ok })
+    await s.consistency()
+    const again = await alice.callSync("app", "messages", "find_or_create_thread", { participant_ids: [bobAddress] })
+    t.ok(again.Ok)
+    t.notEqual(again.Ok, restarted.Ok)
+    t.notEqual(again.Ok, thread)
+  })
+
+scenario('Replies must be in the same thread as the message they reply to', async (s, t) => {
+    const { alice, bob } = await s.players({alice: one, bob: one}, true)
+    const bobAddress = bob.info('app').agentAddress
+
+    const withBob = (await alice.callSync("app", "messages", "find_or_create_thread", { participant_ids: [bobAddress] })).Ok
+    const alone = (await alice.callSync("app", "messages", "find_or_create_thread", { participant_ids: [] })).Ok
+    const original = (await alice.callSync("app", "messages", "create", { thread_address: withBob, text: "to bob", timestamp: "2019-03-29T01:00:00+00:00" })).Ok
+    await s.consistency()
+
+    const reply = await bob.callSync("app", "messages", "create", { thread_address: withBob, text: "back to alice", timestamp: "2019-03-29T01:01:00+00:00", reply_to: original.address })
+    t.deepEqual(reply.Ok.quoted, { address: original.address, creator: original.creator, snippet: "to bob" })
+    const crossThread = await alice.callSync("app", "messages", "create", { thread_address: alone, text: "note to self", timestamp: "2019-03-29T01:02:00+00:00", reply_to: original.address })
+    t.ok(crossThread.Err, "Cannot reply to a message in another thread")
+
+    const bobEdit = await bob.callSync("app", "messages", "edit", { message_addr: original.address, text: "hijacked", timestamp: "2019-03-29T01:03:00+00:00" })
+    t.ok(bobEdit.Err, "Only the creator can edit a message")
+  })
+}
@@ -33,6 +33,140 @@ module.exports = (scenario) => {
 
   	// get all the comments on a base
   	const allResult = await callComments('all_for_base', { base })
-  	t.deepEqual(allResult.Ok.length, 2)
+  	t.deepEqual(allResult.Ok.comments.length, 2)
+  	t.equal(allResult.Ok.more, false)
+  })
+
+  scenario("Reply to comments and get them as a tree", async (s, t) => {
+    const { alice } = await s.players({alice: one}, true)
+    const callComments = (func, params) => alice.callSync("app", "comments", func, params)
+    const base = 'threaded'
+    const comment = (text, parent, second) => callComments('create', {
+      base,
+      text,
+      timestamp: `2019-03-29T01:58:${second}+00:00`,
+      parent
+    })
+
+    const root = (await comment("root", undefined, 10)).Ok
+    const reply1 = (await comment("reply1", root.address, 11)).Ok
+    await comment("reply2", root.address, 12)
+    await comment("nested", reply1.address, 13)
+    t.equal(reply1.parent, root.address)
+
+    // replies are still in the flat listing
+    const allResult = await callComments('all_for_base', { base })
+    t.equal(allResult.Ok.comments.length, 4)
+
+    const thread = (await callComments('thread_for_base', { base, depth: 1, limit: 1 })).Ok
+    t.equal(thread.comments.length, 1)
+    t.equal(thread.comments[0].text, "root")
+    t.equal(thread.more, false)
+    const replies = thread.comments[0].replies
+    t.deepEqual(replies.comments.map(c => c.text), ["reply1"])
+    t.equal(replies.more, true)
+    // past the depth limit we are only told there is more
+    t.deepEqual(replies.comments[0].replies, { comments: [], more: true, cursor: null })
+
+    const moreReplies = await callComments('replies', { parent: root.address, after: replies.cursor })
+    t.deepEqual(moreReplies.Ok.comments.map(c => c.text), ["reply2"])
+    t.equal(moreReplies.Ok.more, false)
+
+    // without a limit every reply is included at every level
+    const unlimited = (await callComments('thread_for_base', { base, depth: 2 })).Ok
+    t.deepEqual(unlimited.comments[0].replies.comments.map(c => c.text), ["reply1", "reply2"])
+    t.equal(unlimited.comments[0].replies.more, false)
+
+    // a reply must be in the same base as its parent
+    const elsewhere = await callComments('create', { base: 'elsewhere', text: "lost", timestamp: "2019-03-29T01:59:00+00:00", parent: root.address })
+    t.ok(elsewhere.Err)
+  })
+
+  scenario("Edit and delete a comment", async (s, t) => {
+    const { alice } = await s.players({alice: one}, true)
+    const callComments = (func, params) => alice.callSync("app", "comments", func, params)
+    const base = 'editable'
+
+    const { address } = (await callComments('create', { ...testComment1, base })).Ok
+    await callComments('create', { ...testComment2, base })
+
+    const editedAt = "2019-03-30T00:00:00+00:00"
+    const updateResult = await callComments('update', { address, text: "fixed typo", timestamp: editedAt })
+    t.equal(updateResult.Ok.text, "fixed typo")
+
+    const getResult = await callComments('get', { address })
+    t.equal(getResult.Ok.text, "fixed typo")
+    t.equal(getResult.Ok.edited_at, editedAt)
+
+    await callComments('delete', { address })
+    const allResult = await callComments('all_for_base', { base })
+    t.deepEqual(allResult.Ok.comments.map(c => c.text), [testComment2.text])
+    const counts = await callComments('count_for_bases', { bases: [base] })
+    t.equal(counts.Ok[0].count, 1)
+
+    // the removed comment stays in the thread without its text
+    const thread = await callComments('thread_for_base', { base })
+    const removed = thread.Ok.comments.find(c => c.address === address)
+    t.equal(removed.text, "")
+    t.ok(removed.removed)
+    const editRemoved = await callComments('update', { address, text: "back", timestamp: editedAt })
+    t.ok(editRemoved.Err)
+  })
+
+  scenario("Page through comments in timestamp order", async (s, t) => {
+    const { alice } = await s.players({alice: one}, true)
+    const callComments = (func, params) => alice.callSync("app", "comments", func, params)
+    const base = 'paged'
+
+    // created out of order
+    for (const second of [13, 10, 12, 11, 14]) {
+      await callComments('create', { base, text: `at ${second}`, timestamp: `2019-03-29T01:58:${second}+00:00` })
+    }
+
+    const firstPage = (await callComments('all_for_base', { base, limit: 2 })).Ok
+    t.deepEqual(firstPage.comments.map(c => c.text), ["at 10", "at 11"])
+    t.equal(firstPage.more, true)
+
+    const nextPage = (await callComments('all_for_base', { base, since: firstPage.comments[1].address, limit: 2 })).Ok
+    t.deepEqual(nextPage.comments.map(c => c.text), ["at 12", "at 13"])
+    t.equal(nextPage.more, true)
+
+    const lastPage = (await callComments('all_for_base', { base, since: nextPage.comments[1].address, limit: 2 })).Ok
+    t.deepEqual(lastPage.comments.map(c => c.text), ["at 14"])
+    t.equal(lastPage.more, false)
+
+    // scrolling back from a comment gives the newest ones before it
+    const earlier = (await callComments('all_for_base', { base, before: lastPage.comments[0].address, limit: 2 })).Ok
+    t.deepEqual(earlier.comments.map(c => c.text), ["at 12", "at 13"])
+    t.equal(earlier.more, true)
+    // a cursor still marks its place after its comment is removed
+    await callComments('delete', { address: nextPage.comments[1].address })
+    const afterRemoved = (await callComments('all_for_base', { base, since: nextPage.comments[1].address })).Ok
+    t.deepEqual(afterRemoved.comments.map(c => c.text), ["at 14"])
+  })
+
+  scenario("Comments must have some text", async (s, t) => {
+    const { alice } = await s.players({alice: one}, true)
+    const emptyResult = await alice.callSync("app", "comments", "create", { ...testComment1, text: "   " })
+    t.ok(emptyResult.Err, "Empty comment is rejected")
+    const tooLongResult = await alice.callSync("app", "comments", "create", { ...testComment1, text: "x".repeat(10001) })
+    t.ok(tooLongResult.Err, "Overly long comment is rejected")
+  })
+
+  scenario("Count comments on several bases at once", async (s, t) => {
+    const { alice } = await s.players({alice: one}, true)
+    const callComments = (func, params) => alice.callSync("app", "comments", func, params)
+
+    const first = (await callComments('create', { base: 'counted1', text: "one", timestamp: "2019-03-29T01:58:10+00:00" })).Ok
+    await callComments('create', { base: 'counted1', text: "two", timestamp: "2019-03-29T01:58:30+00:00" })
+    await callComments('create', { base: 'counted1', text: "reply", timestamp: "2019-03-29T01:58:20+00:00", parent: first.address })
+    await callComments('create', { base: 'counted2', text: "three", timestamp: "2019-03-29T01:58:40+00:00" })
+
+    const countResult = await callComments('count_for_bases', { bases: ['counted1', 'counted2', 'uncommented'] })
+    t.deepEqual(countResult.Ok, [
+      { base: 'counted1', count: 3, latest: "2019-03-29T01:58:30+00:00" },
+      { base: 'counted2', count: 1, latest: "2019-03-29T01:58:40+00:00" },
+      { base: 'uncommented', count: 0, latest: null }
+    ])
   })
 }
@@ -35,4 +35,117 @@ module.exports = scenario => {
     })
     t.ok(get_communities_result.Ok.some(community => community.name === communityResult.name), "Could retrieve the added community from the base")
   })
+
+  scenario("Archive and delete a community", async (s, t) => {
+    const { alice } = await s.players({alice: one}, true)
+    const callCommunities = (func, params) => alice.callSync("app", "communities", func, params)
+
+    const slug = "archive-me"
+    const { address } = (await callCommunities("create", { name: "Archive me", slug })).Ok
+
+    const archiveResult = await callCommunities("archive", { address })
+    t.equal(archiveResult.Ok.status, "archived")
+
+    // hidden from the directory by default but still readable
+    const all = await callCommunities("all", {})
+    t.notOk(all.Ok.some(community => community.address === address), "Archived community is hidden")
+    const allWithArchived = await callCommunities("all", { include_archived: true })
+    t.ok(allWithArchived.Ok.some(community => community.address === address), "Archived community can be listed")
+    const getResult = await callCommunities("get", { address })
+    t.equal(getResult.Ok.status, "archived")
+
+    // no new posts in its base
+    const postResult = await alice.callSync("app", "posts", "create", {
+      base: slug,
+      title: "too late",
+      details: "",
+      post_type: "",
+      announcement: false,
+      timestamp: ""
+    })
+    t.ok(postResult.Err, "Cannot post in an archived community")
+
+    // deleting releases the slug
+    await callCommunities("delete", { address })
+    const bySlug = await callCommunities("get_by_slug", { slug })
+    t.ok(bySlug.Err, "Deleted community is no longer found by slug")
+    const recreated = await callCommunities("create", { name: "New owner", slug })
+    t.ok(recreated.Ok, "Slug can be reused after deletion")
+  })
+
+  scenario("Page through the community directory", async (s, t) => {
+    const { alice } = await s.players({alice: one}, true)
+    const callCommunities = (func, params) => alice.callSync("app", "communities", func, params)
+
+    const names = ["Zebra club", "apple growers", "Mango fans"]
+    for (let i = 0; i < names.length; i++) {
+      await callCommunities("create", { name: names[i], slug: `dir-${i}`, created_at: `2019-10-0${i + 1}T00:00:00+00:00` })
+    }
+    const mango = (await callCommunities("get_by_slug", { slug: "dir-2" })).Ok
+
+    // search is a case insensitive prefix match on name or slug
+    const search = await callCommunities("directory", { search: "DIR-" })
+    t.deepEqual(search.Ok.communities.map(c => c.name), ["apple growers", "Mango fans", "Zebra club"])
+    t.deepEqual(search.Ok.unreadable, [])
+
+    const firstPage = await callCommunities("directory", { search: "dir-", limit: 2, sort_by: "created_at" })
+    t.deepEqual(firstPage.Ok.communities.map(c => c.name), ["Mango fans", "apple growers"])
+    t.equal(firstPage.Ok.more, true)
+    const secondPage = await callCommunities("directory", { search: "dir-", limit: 2, sort_by: "created_at", after: firstPage.Ok.communities[1].address })
+    t.deepEqual(secondPage.Ok.communities.map(c => c.name), ["Zebra club"])
+    t.equal(secondPage.Ok.more, false)
+
+    // the creator is an admin member, and joining again does not add another membership
+    const joinResult = await callCommunities("join", { community_address: mango.address })
+    t.equal(joinResult.Ok.role, "admin")
+    const byMembers = await callCommunities("directory", { search: "dir-", sort_by: "member_count" })
+    t.deepEqual(byMembers.Ok.communities.map(c => c.member_count), [1, 1, 1])
+
+    // a role on top of a membership is still one member
+    await callCommunities("add_moderator", { community_address: mango.address, agent_id: alice.info('app').agentAddress })
+    const afterRole = await callCommunities("directory", { search: "mango" })
+    t.deepEqual(afterRole.Ok.communities.map(c => c.member_count), [1])
+
+    // a cursor that was archived between pages resumes from where it sorted
+    await callCommunities("archive", { address: firstPage.Ok.communities[1].address })
+    const resumed = await callCommunities("directory", { search: "dir-", limit: 2, sort_by: "created_at", after: firstPage.Ok.communities[1].address })
+    t.deepEqual(resumed.Ok.communities.map(c => c.name), ["Zebra club"])
+    t.equal(resumed.Ok.more, false)
+
+    // a cursor that is not a community is an error
+    const stale = await callCommunities("directory", { search: "dir-", after: "QmNotACommunity" })
+    t.ok(stale.Err)
+  })
+
+  scenario("Get statistics for a community", async (s, t) => {
+    const { alice } = await s.players({alice: one}, true)
+    const callCommunities = (func, params) => alice.callSync("app", "communities", func, params)
+
+    const slug = "stats"
+    const { address } = (await callCommunities("create", { name: "Stats", slug })).Ok
+    const post = (timestamp) => alice.callSync("app", "posts", "create", {
+      base: slug,
+      title: "a post",
+      details: "",
+      post_type: "",
+      announcement: false,
+      timestamp
+    })
+    await post("2019-01-01T00:00:00+00:00")
+    await post("2019-06-25T00:00:00+00:00")
+    await post("2019-06-28T00:00:00+00:00")
+
+    const statsResult = await callCommunities("stats", { community: address, now: "2019-07-01T00:00:00+00:00", days: 7 })
+    t.deepEqual(statsResult.Ok, {
+      member_count: 1,
+      post_count: 3,
+      recent_post_count: 2,
+      active_posters: [alice.info('app').agentAddress],
+      days: 7
+    })
+
+    // posts made through create are already indexed
+    const indexed = await alice.callSync("app", "posts", "index_base", { base: slug })
+    t.equal(indexed.Ok, 0)
+  })
 }
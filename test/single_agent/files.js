@@ -0,0 +1,58 @@
+const { one } = require('../config')
+module.exports = (scenario) => {
+
+scenario('Store a file and attach it to a message and a comment', async (s, t) => {
+    const { alice } = await s.players({alice: one}, true)
+    const callFiles = (func, params) => alice.callSync("app", "files", func, params)
+
+    const data = Buffer.from("minutes of the garden meeting").toString('base64')
+    const stored = await callFiles("store", { name: "minutes.txt", mime_type: "text/plain", data })
+    const file = stored.Ok
+    t.equal(file.size, 29)
+    t.equal(file.mime_type, "text/plain")
+    t.equal(file.chunks.length, 1)
+
+    t.deepEqual((await callFiles("get", { address: file.address })).Ok, file)
+    t.equal((await callFiles("get_data", { address: file.address })).Ok.data, data)
+    t.equal((await callFiles("get_chunk", { address: file.chunks[0] })).Ok, data)
+
+    const badType = await callFiles("store", { name: "minutes.txt", mime_type: "text", data })
+    t.ok(badType.Err, "MIME types must be type/subtype")
+
+    const thread = (await alice.callSync("app", "messages", "create_thread", { participant_ids: [] })).Ok
+    const message = await alice.callSync("app", "messages", "create", {
+      thread_address: thread, text: "see attached", timestamp: "2019-03-29T01:00:00+00:00",
+      attachments: [{ name: "minutes.txt", mime_type: "text/plain", data }]
+    })
+    const attached = message.Ok.attachments
+    t.equal(attached.length, 1)
+    t.deepEqual([attached[0].name, attached[0].mime_type, attached[0].size], ["minutes.txt", "text/plain", 29])
+    const read = await alice.callSync("app", "messages", "get_attachment", { message_addr: message.Ok.address, attachment: attached[0].address })
+    t.equal(read.Ok.data, data)
+    t.deepEqual(read.Ok.attachment, attached[0])
+
+    // the files zome only has the encrypted file, under a name and type that give nothing away
+    const encrypted = (await callFiles("get_data", { address: attached[0].address })).Ok
+    t.notEqual(encrypted.data, data)
+    t.equal(encrypted.file.name, "attachment")
+    t.equal(encrypted.file.mime_type, "application/octet-stream")
+
+    const notAttached = await alice.callSync("app", "messages", "get_attachment", { message_addr: message.Ok.address, attachment: file.address })
+    t.ok(notAttached.Err, "Only a message's own attachments can be read through it")
+
+    const comment = await alice.callSync("app", "comments", "create", {
+      base: "base1", text: "see attached", timestamp: "2019-03-29T01:00:00+00:00", attachments: [file.address]
+    })
+    t.deepEqual(comment.Ok.attachments, [file.address])
+
+    const notAFile = await alice.callSync("app", "comments", "create", {
+      base: "base1", text: "see attached", timestamp: "2019-03-29T01:00:00+00:00", attachments: [thread]
+    })
+    t.ok(notAFile.Err, "Only stored files can be attached")
+
+    const twice = await alice.callSync("app", "comments", "create", {
+      base: "base1", text: "see attached", timestamp: "2019-03-29T01:00:00+00:00", attachments: [file.address, file.address]
+    })
+    t.ok(twice.Err, "A file can only be attached once")
+  })
+}
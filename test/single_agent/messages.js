@@ -37,4 +37,55 @@ scenario('Can create a message and retrieve it', async (s, t) => {
     t.deepEqual(get_message_result.Ok, {...testMessage, creator: alice.info('app').agentAddress, address})
 
   })
+
+scenario('Page through the messages in a thread', async (s, t) => {
+  const { alice } = await s.players({alice: one}, true)
+    const callMessages = (func, params) => alice.callSync("app", "messages", func, params)
+    const threadAddress = (await callMessages("create_thread", { participant_ids: [] })).Ok
+
+    // posted out of order
+    for (const n of [3, 1, 4, 2, 5]) {
+      await callMessages("create", { thread_address: threadAddress, text: `message ${n}`, timestamp: `2019-03-29T01:58:0${n}+00:00` })
+    }
+
+    // the first page is the latest messages
+    const latest = (await callMessages("get_thread_messages_page", { thread_address: threadAddress, limit: 2 })).Ok
+    t.deepEqual(latest.messages.map(m => m.text), ["message 4", "message 5"])
+    t.equal(latest.more, true)
+
+    const older = (await callMessages("get_thread_messages_page", { thread_address: threadAddress, before: latest.messages[0].address, limit: 2 })).Ok
+    t.deepEqual(older.messages.map(m => m.text), ["message 2", "message 3"])
+    t.equal(older.more, true)
+
+    const newer = (await callMessages("get_thread_messages_page", { thread_address: threadAddress, after: older.messages[1].address })).Ok
+    t.deepEqual(newer.messages.map(m => m.text), ["message 4", "message 5"])
+    t.equal(newer.more, false)
+    // ordered by time, not by how the timestamp is written: 01:58:03.5 UTC in another offset
+    await callMessages("create", { thread_address: threadAddress, text: "message 3.5", timestamp: "2019-03-29T02:58:03.5+01:00" })
+    const between = (await callMessages("get_thread_messages_page", { thread_address: threadAddress, after: older.messages[1].address, limit: 1 })).Ok
+    t.deepEqual(between.messages.map(m => m.text), ["message 3.5"])
+    t.equal(between.more, true)
+  })
+
+scenario('Edit, unsend and reply to messages', async (s, t) => {
+  const { alice } = await s.players({alice: one}, true)
+    const callMessages = (func, params) => alice.callSync("app", "messages", func, params)
+    const threadAddress = (await callMessages("create_thread", { participant_ids: [] })).Ok
+
+    const original = (await callMessages("create", { thread_address: threadAddress, text: "hello wrold", timestamp: "2019-03-29T01:00:00+00:00" })).Ok
+    const edited = await callMessages("edit", { message_addr: original.address, text: "hello world", timestamp: "2019-03-29T01:01:00+00:00" })
+    t.equal(edited.Ok.text, "hello world")
+    t.equal(edited.Ok.edited_at, "2019-03-29T01:01:00+00:00")
+    t.equal((await callMessages("get", { message_addr: original.address })).Ok.text, "hello world")
+
+    const reply = (await callMessages("create", { thread_address: threadAddress, text: "replying", timestamp: "2019-03-29T01:02:00+00:00", reply_to: original.address })).Ok
+    t.equal(reply.reply_to, original.address)
+    t.deepEqual(reply.quoted, { address: original.address, creator: original.creator, snippet: "hello world" })
+
+    await callMessages("unsend", { message_addr: original.address, timestamp: "2019-03-29T01:04:00+00:00" })
+    t.ok((await callMessages("get", { message_addr: original.address })).Err)
+    const remaining = (await callMessages("get_thread_messages", { thread_address: threadAddress })).Ok
+    t.deepEqual(remaining.map(m => m.text), ["replying"])
+    t.equal(remaining[0].quoted, undefined, "Unsent messages are no longer quoted")
+  })
 }
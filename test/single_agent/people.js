@@ -37,4 +37,131 @@ module.exports = (scenario) => {
     t.deepEqual(allResult.Ok.sort().map(p => p.name), [aliceUser, bobUser].sort().map(p => p.name))
 
   })
+
+  scenario('Update a profile and see its history', async (s, t) => {
+    const { alice } = await s.players({alice: one}, true)
+    const agentAddress = alice.info('app').agentAddress
+
+    await alice.callSync("app", 'people', 'register_user', { name: 'Alice', avatar_url: 'alice.png' })
+    const updated = await alice.callSync("app", 'people', 'update_profile', { name: 'Alice Liddell', avatar_url: 'alice2.png' })
+    t.deepEqual(updated.Ok, { name: 'Alice Liddell', avatar_url: 'alice2.png', address: agentAddress })
+    t.deepEqual((await alice.callSync("app", 'people', 'get_me', {})).Ok, updated.Ok)
+
+    // registering again updates rather than adding a second profile
+    await alice.callSync("app", 'people', 'register_user', { name: 'Al', avatar_url: 'alice3.png' })
+    t.equal((await alice.callSync("app", 'people', 'get_me', {})).Ok.name, 'Al')
+    t.equal((await alice.callSync("app", 'people', 'all', {})).Ok.length, 1)
+
+    const history = (await alice.callSync("app", 'people', 'get_history', { agent_id: agentAddress })).Ok
+    t.deepEqual(history.map(version => version.name), ['Alice', 'Alice Liddell', 'Al'])
+  })
+
+  scenario('Claim a unique handle and be found by it', async (s, t) => {
+    const { alice, bob } = await s.players({alice: one, bob: one}, true)
+    const aliceAddress = alice.info('app').agentAddress
+
+    t.equal((await alice.callSync("app", 'people', 'is_handle_available', { handle: 'alice' })).Ok, true)
+    const registered = await alice.callSync("app", 'people', 'register_user', { name: 'Alice', avatar_url: 'alice.png', handle: '@Alice' })
+    t.deepEqual(registered.Ok, { name: 'Alice', avatar_url: 'alice.png', handle: 'alice', address: aliceAddress })
+    await s.consistency()
+
+    t.equal((await bob.callSync("app", 'people', 'is_handle_available', { handle: 'ALICE' })).Ok, false)
+    t.equal((await bob.callSync("app", 'people', 'get_by_handle', { handle: '@alice' })).Ok.address, aliceAddress)
+
+    // taken, and badly formed, handles are refused
+    const taken = await bob.callSync("app", 'people', 'register_user', { name: 'Bob', avatar_url: '', handle: 'alice' })
+    t.ok(taken.Err)
+    const invalid = await bob.callSync("app", 'people', 'register_user', { name: 'Bob', avatar_url: '', handle: '1 bob!' })
+    t.ok(invalid.Err)
+
+    // changing handle frees the old one
+    await alice.callSync("app", 'people', 'update_profile', { name: 'Alice', avatar_url: 'alice.png', handle: 'alice_l' })
+    await s.consistency()
+    t.equal((await bob.callSync("app", 'people', 'is_handle_available', { handle: 'alice' })).Ok, true)
+    t.equal((await bob.callSync("app", 'people', 'get_by_handle', { handle: 'alice_l' })).Ok.name, 'Alice')
+  })
+
+  scenario('Handles claimed at the same time end up with one owner', async (s, t) => {
+    const { alice, bob } = await s.players({alice: one, bob: one}, true)
+
+    // neither has seen the other's claim yet
+    await alice.callSync("app", 'people', 'register_user', { name: 'Alice', avatar_url: '', handle: 'twin' })
+    await bob.callSync("app", 'people', 'register_user', { name: 'Bob', avatar_url: '', handle: 'twin' })
+    await s.consistency()
+
+    const aliceSees = (await alice.callSync("app", 'people', 'get_by_handle', { handle: 'twin' })).Ok
+    const bobSees = (await bob.callSync("app", 'people', 'get_by_handle', { handle: 'twin' })).Ok
+    t.equal(aliceSees.address, bobSees.address)
+    t.ok([alice.info('app').agentAddress, bob.info('app').agentAddress].includes(aliceSees.address))
+
+    // only the owner's profile shows the handle
+    const loser = aliceSees.address === alice.info('app').agentAddress ? bob : alice
+    const loserProfile = (await alice.callSync("app", 'people', 'get', { agent_id: loser.info('app').agentAddress })).Ok
+    t.equal(loserProfile.handle, undefined)
+    t.equal(aliceSees.handle, 'twin')
+  })
+
+  scenario('Add a bio, links and skills to a profile', async (s, t) => {
+    const { alice } = await s.players({alice: one}, true)
+    const agentAddress = alice.info('app').agentAddress
+
+    const details = {
+      bio: 'Gardener and organiser',
+      location: 'Wellington',
+      website: 'https://alice.example.com',
+      social_links: [{ name: 'twitter', url: 'https://twitter.com/alice' }],
+      skills: ['composting', 'facilitation'],
+      interests: ['permaculture'],
+      banner_url: 'https://alice.example.com/banner.png'
+    }
+    const registered = await alice.callSync("app", 'people', 'register_user', { name: 'Alice', avatar_url: 'alice.png', details })
+    t.deepEqual(registered.Ok, { name: 'Alice', avatar_url: 'alice.png', ...details, address: agentAddress })
+
+    // leaving details out keeps them
+    await alice.callSync("app", 'people', 'update_profile', { name: 'Alice L', avatar_url: 'alice.png' })
+    t.deepEqual((await alice.callSync("app", 'people', 'get_me', {})).Ok.skills, details.skills)
+
+    const badWebsite = await alice.callSync("app", 'people', 'update_profile', { name: 'Alice', avatar_url: '', details: { website: 'javascript:alert(1)' } })
+    t.ok(badWebsite.Err)
+    const longBio = await alice.callSync("app", 'people', 'update_profile', { name: 'Alice', avatar_url: '', details: { bio: 'a'.repeat(1001) } })
+    t.ok(longBio.Err)
+
+    // clearing them leaves a profile like the ones from before details existed
+    const cleared = await alice.callSync("app", 'people', 'update_profile', { name: 'Alice', avatar_url: 'alice.png', details: {} })
+    t.deepEqual(cleared.Ok, { name: 'Alice', avatar_url: 'alice.png', address: agentAddress })
+  })
+
+  scenario('Search and page through the people directory', async (s, t) => {
+    const { alice, bob } = await s.players({alice: one, bob: one}, true)
+
+    await alice.callSync("app", 'people', 'register_user', { name: 'Alice Liddell', avatar_url: '', handle: 'wonder' })
+    await bob.callSync("app", 'people', 'register_user', { name: 'Albert', avatar_url: '' })
+    await s.consistency()
+
+    const firstPage = await alice.callSync("app", 'people', 'directory', { limit: 1 })
+    t.deepEqual(firstPage.Ok.people.map(p => p.name), ['Albert'])
+    t.equal(firstPage.Ok.more, true)
+    const secondPage = await alice.callSync("app", 'people', 'directory', { after: firstPage.Ok.people[0].address, limit: 1 })
+    t.deepEqual(secondPage.Ok.people.map(p => p.name), ['Alice Liddell'])
+    t.equal(secondPage.Ok.more, false)
+
+    const byName = await alice.callSync("app", 'people', 'directory', { search: 'Al' })
+    t.deepEqual(byName.Ok.people.map(p => p.name), ['Albert', 'Alice Liddell'])
+    t.deepEqual((await alice.callSync("app", 'people', 'directory', { search: 'alice l' })).Ok.people.map(p => p.name), ['Alice Liddell'])
+    t.deepEqual((await bob.callSync("app", 'people', 'directory', { search: '@wond' })).Ok.people.map(p => p.name), ['Alice Liddell'])
+    // a search shorter than the bucket prefix scans every bucket starting with it
+    t.deepEqual((await alice.callSync("app", 'people', 'directory', { search: 'a' })).Ok.people.map(p => p.name), ['Albert', 'Alice Liddell'])
+    t.deepEqual((await alice.callSync("app", 'people', 'directory', { search: 'w' })).Ok.people.map(p => p.name), ['Alice Liddell'])
+
+    // renaming moves the listing
+    await bob.callSync("app", 'people', 'update_profile', { name: 'Bert', avatar_url: '' })
+    await s.consistency()
+    t.deepEqual((await alice.callSync("app", 'people', 'directory', { search: 'al' })).Ok.people.map(p => p.name), ['Alice Liddell'])
+    t.deepEqual((await alice.callSync("app", 'people', 'directory', { search: 'be' })).Ok.people.map(p => p.name), ['Bert'])
+
+    await bob.callSync("app", 'people', 'update_profile', { name: 'B', avatar_url: '' })
+    await s.consistency()
+    t.deepEqual((await alice.callSync("app", 'people', 'directory', { search: 'b' })).Ok.people.map(p => p.name), ['B'])
+    t.deepEqual((await alice.callSync("app", 'people', 'directory', {})).Ok.people.map(p => p.name), ['Alice Liddell', 'B'])
+  })
 }
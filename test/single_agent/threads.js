@@ -17,4 +17,49 @@ scenario('Check for a non existent thread and then create it', async (s, t) => {
     console.log(get_result_post)
     t.equal(get_result_post.Ok.length, 1) // created a single thread
   })
+
+scenario('Find or create a thread returns the same thread for the same participants', async (s, t) => {
+    const { alice } = await s.players({alice: one}, true)
+    const agentAddress = alice.info('app').agentAddress
+
+    const first = await alice.callSync("app", "messages", "find_or_create_thread", { participant_ids: [] })
+    t.equal(first.Ok.length, 46)
+
+    // the caller is always a participant, and duplicates are ignored
+    const second = await alice.callSync("app", "messages", "find_or_create_thread", { participant_ids: [agentAddress, agentAddress] })
+    t.equal(second.Ok, first.Ok)
+
+    const threads = await alice.callSync("app", "messages", "get_threads", {})
+    t.equal(threads.Ok.length, 1)
+  })
+
+scenario('Create a named thread and rename it', async (s, t) => {
+    const { alice } = await s.players({alice: one}, true)
+    const agentAddress = alice.info('app').agentAddress
+
+    const thread = (await alice.callSync("app", "messages", "create_thread", {
+      participant_ids: [], name: "Planning", image_url: "planning.png", created_at: "2019-03-29T01:00:00+00:00"
+    })).Ok
+
+    const renamed = await alice.callSync("app", "messages", "update_thread_details", { thread_address: thread, name: "Garden planning", image_url: "garden.png" })
+    t.equal(renamed.Ok.name, "Garden planning")
+
+    const threads = (await alice.callSync("app", "messages", "get_threads_with_details", {})).Ok
+    t.deepEqual(threads, [{
+      address: thread,
+      participants: [agentAddress],
+      name: "Garden planning",
+      image_url: "garden.png",
+      creator: agentAddress,
+      created_at: "2019-03-29T01:00:00+00:00"
+    }])
+
+    // leaving out the picture keeps it
+    const renamedAgain = await alice.callSync("app", "messages", "update_thread_details", { thread_address: thread, name: "Allotment" })
+    t.equal(renamedAgain.Ok.name, "Allotment")
+    t.equal(renamedAgain.Ok.image_url, "garden.png")
+
+    const tooLong = await alice.callSync("app", "messages", "update_thread_details", { thread_address: thread, name: "x".repeat(101) })
+    t.ok(tooLong.Err, "Thread names have a maximum length")
+  })
 }
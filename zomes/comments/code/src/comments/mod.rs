@@ -7,18 +7,26 @@
  */
 use hdk::{
     entry_definition::ValidatingEntryType,
-    error::ZomeApiResult,
+    error::{ZomeApiError, ZomeApiResult},
     holochain_core_types::{
         dna::entry_types::Sharing, entry::Entry, link::LinkMatch, time::Iso8601,
+        validation::EntryValidationData,
     },
     holochain_json_api::{
         error::JsonError,
         json::{JsonString, RawString},
     },
-    holochain_persistence_api::cas::content::Address,
-    utils, AGENT_ADDRESS,
+    holochain_persistence_api::cas::content::{Address, AddressableContent},
+    utils, LinkValidationData, AGENT_ADDRESS,
 };
-use hdk_helpers::commit_if_not_in_chain;
+use hdk_helpers::{
+    commit_if_not_in_chain,
+    files::{check_attachments, validate_attachments},
+    link_mentions, link_new_mentions, validate_mention_link, MENTION_LINK_TYPE,
+};
+use std::{cmp::Ordering, convert::TryFrom};
+
+use crate::moderation;
 
 // tag for links from base to comment
 
@@ -27,6 +35,47 @@ pub type Base = String;
 pub const COMMENT_ENTRY_TYPE: &str = "comment";
 pub const BASE_ENTRY_TYPE: &str = "base";
 pub const COMMENT_LINK_TYPE: &str = "commented_on";
+pub const REPLY_LINK_TYPE: &str = "replied_with";
+
+// Links from a base to its comments are tagged "<kind>:<timestamp>" so top level comments can be
+// fetched without the replies, and counts and latest times can be read without loading any comments.
+// Removed comments have "removed-" in front of the kind and "|<address of the removed version>" after the
+// timestamp so validators can check the removal. Comments made before this have an empty tag and are top level
+const TOP_LEVEL_TAG_PREFIX: &str = "comment";
+const REPLY_TAG_PREFIX: &str = "reply";
+const REMOVED_TAG_PREFIX: &str = "removed-";
+const REMOVAL_TAG_SEPARATOR: char = '|';
+const TOP_LEVEL_TAG_PATTERN: &str = "^((removed-)?comment:.*)?$";
+
+fn base_link_tag(comment: &Comment) -> String {
+    let prefix = match comment.parent {
+        Some(_) => REPLY_TAG_PREFIX,
+        None => TOP_LEVEL_TAG_PREFIX,
+    };
+    let removed = match comment.removed {
+        Some(_) => REMOVED_TAG_PREFIX,
+        None => "",
+    };
+    format!("{}{}:{}", removed, prefix, comment.timestamp)
+}
+
+fn removed_link_tag(removed: &Comment, removed_version: &Address) -> String {
+    format!(
+        "{}{}{}",
+        base_link_tag(removed),
+        REMOVAL_TAG_SEPARATOR,
+        removed_version
+    )
+}
+
+fn timestamp_from_tag(tag: &str) -> Option<Iso8601> {
+    tag.split(REMOVAL_TAG_SEPARATOR)
+        .next()
+        .unwrap_or(tag)
+        .splitn(2, ':')
+        .nth(1)
+        .and_then(|timestamp| Iso8601::try_from(timestamp).ok())
+}
 
 // comment type and result format
 
@@ -36,6 +85,27 @@ pub struct Comment {
     creator: Address,
     text: String,
     timestamp: Iso8601,
+    // the comment this is a reply to. Left out when serializing top level comments so they keep their address
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    parent: Option<Address>,
+    // set when the text has been changed with `update`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    edited_at: Option<Iso8601>,
+    // addresses of files stored with the files zome
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    attachments: Vec<Address>,
+    // set when the comment has been deleted. Its text and attachments are emptied but it stays in
+    // place so the replies to it are not orphaned
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    removed: Option<Removal>,
+}
+
+// who removed a comment, and for a moderator the community they moderate
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Removal {
+    by: Address,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    community: Option<Address>,
 }
 
 // Converts a comment (without address) into a comment result for returning from the api call
@@ -47,6 +117,10 @@ impl Comment {
             text: self.text.clone(),
             timestamp: self.timestamp.clone(),
             creator: self.creator.clone(),
+            parent: self.parent.clone(),
+            edited_at: self.edited_at.clone(),
+            attachments: self.attachments.clone(),
+            removed: self.removed.clone(),
         }
     }
 }
@@ -58,17 +132,68 @@ pub struct CommentWithAddress {
     creator: Address,
     text: String,
     timestamp: Iso8601,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    parent: Option<Address>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    edited_at: Option<Iso8601>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    attachments: Vec<Address>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    removed: Option<Removal>,
+}
+
+#[derive(Serialize, Deserialize, Debug, DefaultJson, Clone)]
+pub struct GetCommentsResult {
+    comments: Vec<CommentWithAddress>,
+    more: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, DefaultJson, Clone)]
+pub struct CommentCount {
+    base: String,
+    count: usize,
+    latest: Option<Iso8601>,
+}
+
+// One page of comments at one level of a thread
+#[derive(Serialize, Deserialize, Debug, DefaultJson, Clone)]
+pub struct CommentThread {
+    comments: Vec<CommentNode>,
+    // true if there are comments at this level that are not included
+    more: bool,
+    // pass as `after` to load the rest of this level. None with `more` set means start from the beginning
+    cursor: Option<Address>,
+}
+
+#[derive(Serialize, Deserialize, Debug, DefaultJson, Clone)]
+pub struct CommentNode {
+    #[serde(flatten)]
+    comment: CommentWithAddress,
+    replies: CommentThread,
 }
 
 // API methods
 
-pub fn create(base: String, text: String, timestamp: Iso8601) -> ZomeApiResult<CommentWithAddress> {
+pub fn create(
+    base: String,
+    text: String,
+    timestamp: Iso8601,
+    parent: Option<Address>,
+    attachments: Option<Vec<Address>>,
+) -> ZomeApiResult<CommentWithAddress> {
+    let attachments = attachments.unwrap_or_default();
+    check_attachments(&attachments)?;
+
     // create and store the comment
     let comment = Comment {
         base: base.clone(),
         text: text.clone(),
         timestamp: timestamp.clone(),
         creator: AGENT_ADDRESS.to_string().into(),
+        parent: parent.clone(),
+        edited_at: None,
+        attachments,
+        removed: None,
     };
     let entry = Entry::App(COMMENT_ENTRY_TYPE.into(), comment.clone().into());
     let address = hdk::commit_entry(&entry)?;
@@ -77,8 +202,18 @@ pub fn create(base: String, text: String, timestamp: Iso8601) -> ZomeApiResult<C
     let base_entry = Entry::App(BASE_ENTRY_TYPE.into(), RawString::from(base.clone()).into());
     let base_address = commit_if_not_in_chain(&base_entry)?;
 
-    // link the comment to its originating thing
-    hdk::link_entries(&base_address, &address, COMMENT_LINK_TYPE, "")?;
+    // link the comment to its originating thing, and replies to their parent as well
+    hdk::link_entries(
+        &base_address,
+        &address,
+        COMMENT_LINK_TYPE,
+        &base_link_tag(&comment),
+    )?;
+    if let Some(parent) = &parent {
+        hdk::link_entries(parent, &address, REPLY_LINK_TYPE, "")?;
+    }
+
+    link_mentions(&text, &address, COMMENT_ENTRY_TYPE, &timestamp.to_string());
 
     Ok(comment.with_address(address))
 }
@@ -87,20 +222,592 @@ pub fn get(address: Address) -> ZomeApiResult<CommentWithAddress> {
     utils::get_as_type::<Comment>(address.clone()).map(|comment| comment.with_address(address))
 }
 
-pub fn all_for_base(base: String) -> ZomeApiResult<Vec<CommentWithAddress>> {
-    let address = hdk::entry_address(&Entry::App(
+// Change the text of a comment. Only the creator can do this
+pub fn update(
+    address: Address,
+    text: String,
+    timestamp: Iso8601,
+) -> ZomeApiResult<CommentWithAddress> {
+    let latest = match hdk::get_entry(&address)? {
+        Some(entry) => entry,
+        None => return Err(ZomeApiError::Internal("Comment not found".into())),
+    };
+    let comment = utils::get_as_type::<Comment>(address.clone())?;
+    if comment.creator != Address::from(AGENT_ADDRESS.to_string()) {
+        return Err(ZomeApiError::Internal(
+            "Only the creator of a comment can edit it".into(),
+        ));
+    }
+    if comment.removed.is_some() {
+        return Err(ZomeApiError::Internal(
+            "A removed comment cannot be edited".into(),
+        ));
+    }
+    let previous_text = comment.text.clone();
+    let updated = Comment {
+        text,
+        edited_at: Some(timestamp.clone()),
+        ..comment
+    };
+    hdk::update_entry(
+        Entry::App(COMMENT_ENTRY_TYPE.into(), updated.clone().into()),
+        &latest.address(),
+    )?;
+
+    link_new_mentions(
+        &previous_text,
+        &updated.text,
+        &address,
+        COMMENT_ENTRY_TYPE,
+        &timestamp.to_string(),
+    );
+
+    Ok(updated.with_address(address))
+}
+
+// Remove a comment. Allowed for its creator and for moderators of the community it was made in.
+// The comment is emptied rather than deleted so replies to it keep their place in the thread
+pub fn delete(address: Address) -> ZomeApiResult<Address> {
+    let latest = match hdk::get_entry(&address)? {
+        Some(entry) => entry,
+        None => return Err(ZomeApiError::Internal("Comment not found".into())),
+    };
+    let comment = utils::get_as_type::<Comment>(address.clone())?;
+    if comment.removed.is_some() {
+        return Ok(address);
+    }
+    let agent_id = Address::from(AGENT_ADDRESS.to_string());
+    // a moderator names the community they moderate so validators don't have to look it up
+    let community = if comment.creator == agent_id {
+        None
+    } else {
+        match moderation::community_for_comment_base(&comment.base)? {
+            Some(community) if moderation::can_moderate(&comment.base, &community, &agent_id)? => {
+                Some(community)
+            }
+            _ => {
+                return Err(ZomeApiError::Internal(
+                    "Only the creator or a moderator can delete a comment".into(),
+                ))
+            }
+        }
+    };
+    let removed = Comment {
+        text: String::new(),
+        attachments: Vec::new(),
+        removed: Some(Removal {
+            by: agent_id,
+            community,
+        }),
+        ..comment
+    };
+    let removed_version = hdk::update_entry(
+        Entry::App(COMMENT_ENTRY_TYPE.into(), removed.clone().into()),
+        &latest.address(),
+    )?;
+
+    // retag the link from the base so listings and counts can skip the comment without loading it
+    let base_address = base_address(removed.base.clone())?;
+    for link in hdk::get_links(
+        &base_address,
+        LinkMatch::Exactly(COMMENT_LINK_TYPE),
+        LinkMatch::Any,
+    )?
+    .links()
+    {
+        if link.address == address {
+            hdk::remove_link(&base_address, &address, COMMENT_LINK_TYPE, &link.tag)?;
+        }
+    }
+    hdk::link_entries(
+        &base_address,
+        &address,
+        COMMENT_LINK_TYPE,
+        &removed_link_tag(&removed, &removed_version),
+    )?;
+    Ok(address)
+}
+
+/**
+ * @brief      Get the comments on a base, oldest first. The page is worked out from the link tags so only
+ *             the comments returned are loaded
+ *
+ * @param      since       Address of a comment. Only comments after it are returned. It still marks its place
+ *                         if the comment has since been removed
+ *
+ * @param      before      Address of a comment. Only comments before it are returned
+ *
+ * @param      limit       Maximum number of comments to return. If only `before` is given these are the
+ *                         newest ones before it (for scrolling back), otherwise the oldest ones
+ *
+ * @return     The comments and a bool which is true if the limit left some out
+ */
+pub fn all_for_base(
+    base: String,
+    since: Option<Address>,
+    before: Option<Address>,
+    limit: Option<usize>,
+) -> ZomeApiResult<GetCommentsResult> {
+    let positions = positions(&base_address(base)?)?;
+    let since = since
+        .map(|cursor| cursor_position(&positions, cursor))
+        .transpose()?;
+    let before = before
+        .map(|cursor| cursor_position(&positions, cursor))
+        .transpose()?;
+
+    let mut page: Vec<&Position> = positions
+        .iter()
+        .filter(|position| !position.removed)
+        .filter(|position| {
+            since.as_ref().map_or(true, |since| {
+                compare_positions(position, since) == Ordering::Greater
+            }) && before.as_ref().map_or(true, |before| {
+                compare_positions(position, before) == Ordering::Less
+            })
+        })
+        .collect();
+
+    let limit = limit.unwrap_or(page.len());
+    let more = page.len() > limit;
+    if since.is_none() && before.is_some() {
+        page.drain(..page.len().saturating_sub(limit));
+    } else {
+        page.truncate(limit);
+    }
+
+    // skip any that can't be loaded rather than failing the whole list
+    let comments = page
+        .into_iter()
+        .filter_map(|position| get(position.address.clone()).ok())
+        .collect();
+
+    Ok(GetCommentsResult { comments, more })
+}
+
+/**
+ * @brief      Count the comments on each of the bases, replies included and removed comments left out.
+ *             Reads only the links from each base, never the comments themselves
+ *
+ * @return     For each base, in the same order, its comment count and the timestamp of its latest comment
+ */
+pub fn count_for_bases(bases: Vec<String>) -> ZomeApiResult<Vec<CommentCount>> {
+    bases
+        .into_iter()
+        .map(|base| {
+            let links = hdk::get_links(
+                &base_address(base.clone())?,
+                LinkMatch::Exactly(COMMENT_LINK_TYPE),
+                LinkMatch::Any,
+            )?
+            .links()
+            .into_iter()
+            .filter(|link| !link.tag.starts_with(REMOVED_TAG_PREFIX))
+            .collect::<Vec<_>>();
+            let latest = links
+                .iter()
+                .filter_map(|link| timestamp_from_tag(&link.tag))
+                .max();
+            Ok(CommentCount {
+                base,
+                count: links.len(),
+                latest,
+            })
+        })
+        .collect()
+}
+
+fn base_address(base: String) -> ZomeApiResult<Address> {
+    hdk::entry_address(&Entry::App(
         BASE_ENTRY_TYPE.into(),
         RawString::from(base).into(),
-    ))?;
-    Ok(hdk::get_links(
-        &address,
-        LinkMatch::Exactly(COMMENT_LINK_TYPE.into()),
+    ))
+}
+
+// Where a comment sits in the listing of its base, read from the tag of the link to it
+#[derive(Clone)]
+struct Position {
+    address: Address,
+    timestamp: Iso8601,
+    removed: bool,
+}
+
+// Every comment linked from a base in listing order, removed ones included.
+// Only comments linked before the links were tagged need to be loaded
+fn positions(base_address: &Address) -> ZomeApiResult<Vec<Position>> {
+    let mut positions: Vec<Position> = hdk::get_links(
+        base_address,
+        LinkMatch::Exactly(COMMENT_LINK_TYPE),
         LinkMatch::Any,
     )?
-    .addresses()
-    .iter()
-    .map(|address| get(address.to_string().into()).unwrap())
-    .collect())
+    .links()
+    .into_iter()
+    .filter_map(|link| match timestamp_from_tag(&link.tag) {
+        Some(timestamp) => Some(Position {
+            removed: link.tag.starts_with(REMOVED_TAG_PREFIX),
+            address: link.address,
+            timestamp,
+        }),
+        None => get(link.address.clone()).ok().map(|comment| Position {
+            removed: comment.removed.is_some(),
+            address: link.address,
+            timestamp: comment.timestamp,
+        }),
+    })
+    .collect();
+    positions.sort_by(compare_positions);
+    // while a removal is being retagged both links can be seen. The removed one wins, which is safe
+    // as validation only accepts a removed tag that names a removal of the comment
+    positions.dedup_by(|later, kept| {
+        if later.address == kept.address {
+            kept.removed |= later.removed;
+            true
+        } else {
+            false
+        }
+    });
+    Ok(positions)
+}
+
+// Removed comments keep their link, so cursors pointing at them are still found
+fn cursor_position(positions: &[Position], cursor: Address) -> ZomeApiResult<Position> {
+    match positions.iter().find(|position| position.address == cursor) {
+        Some(position) => Ok(position.clone()),
+        None => get(cursor).map(|comment| Position {
+            address: comment.address,
+            timestamp: comment.timestamp,
+            removed: comment.removed.is_some(),
+        }),
+    }
+}
+
+fn compare_positions(a: &Position, b: &Position) -> Ordering {
+    a.timestamp
+        .cmp(&b.timestamp)
+        .then_with(|| a.address.to_string().cmp(&b.address.to_string()))
+}
+
+// oldest first. Ties are broken on address so every agent sees the same order
+fn compare_comments(a: &CommentWithAddress, b: &CommentWithAddress) -> Ordering {
+    a.timestamp
+        .cmp(&b.timestamp)
+        .then_with(|| a.address.to_string().cmp(&b.address.to_string()))
+}
+
+fn sort_comments(comments: &mut Vec<CommentWithAddress>) {
+    comments.sort_by(compare_comments);
+}
+
+fn load_level(
+    links_base: &Address,
+    link_type: &str,
+    tag: LinkMatch<&str>,
+    depth: usize,
+    limit: Option<usize>,
+    after: Option<Address>,
+) -> ZomeApiResult<CommentThread> {
+    let mut comments: Vec<CommentWithAddress> =
+        hdk::get_links(links_base, LinkMatch::Exactly(link_type), tag)?
+            .addresses()
+            .into_iter()
+            .filter_map(|address| get(address).ok())
+            .collect();
+    sort_comments(&mut comments);
+
+    let start = match after {
+        Some(after) => comments
+            .iter()
+            .position(|comment| comment.address == after)
+            .map(|position| position + 1)
+            .ok_or(ZomeApiError::Internal(
+                "Cursor does not match any comment".into(),
+            ))?,
+        None => 0,
+    };
+    let page_size = limit.unwrap_or(comments.len());
+    let more = comments.len() > start + page_size;
+
+    let nodes = comments
+        .into_iter()
+        .skip(start)
+        .take(page_size)
+        .map(|comment| {
+            let replies = if depth > 0 {
+                load_level(
+                    &comment.address,
+                    REPLY_LINK_TYPE,
+                    LinkMatch::Any,
+                    depth - 1,
+                    limit,
+                    None,
+                )?
+            } else {
+                // past the requested depth only say whether there are replies to load
+                CommentThread {
+                    comments: Vec::new(),
+                    more: hdk::get_links_count(
+                        &comment.address,
+                        LinkMatch::Exactly(REPLY_LINK_TYPE),
+                        LinkMatch::Any,
+                    )?
+                    .count
+                        > 0,
+                    cursor: None,
+                }
+            };
+            Ok(CommentNode { comment, replies })
+        })
+        .collect::<ZomeApiResult<Vec<_>>>()?;
+
+    let cursor = if more {
+        nodes.last().map(|node| node.comment.address.clone())
+    } else {
+        None
+    };
+    Ok(CommentThread {
+        comments: nodes,
+        more,
+        cursor,
+    })
+}
+
+/**
+ * @brief      Get the comments on a base as a tree of replies. Removed comments are included, without their
+ *             text, so their replies stay in place
+ *
+ * @param      depth       Levels of replies to include below the top level comments. 0 returns only top level comments
+ *
+ * @param      limit       Maximum number of comments to return at each level of each branch
+ *
+ * @param      after       Cursor from a previous call to continue the top level from
+ */
+pub fn thread_for_base(
+    base: String,
+    depth: Option<usize>,
+    limit: Option<usize>,
+    after: Option<Address>,
+) -> ZomeApiResult<CommentThread> {
+    load_level(
+        &base_address(base)?,
+        COMMENT_LINK_TYPE,
+        LinkMatch::Regex(TOP_LEVEL_TAG_PATTERN),
+        depth.unwrap_or(0),
+        limit,
+        after,
+    )
+}
+
+/**
+ * @brief      Load (more of) the replies to a comment, as returned in its `replies` by thread_for_base
+ */
+pub fn replies(
+    parent: Address,
+    depth: Option<usize>,
+    limit: Option<usize>,
+    after: Option<Address>,
+) -> ZomeApiResult<CommentThread> {
+    load_level(
+        &parent,
+        REPLY_LINK_TYPE,
+        LinkMatch::Any,
+        depth.unwrap_or(0),
+        limit,
+        after,
+    )
+}
+
+// Validation
+
+const MAX_COMMENT_LENGTH: usize = 10_000;
+// DNA property that, when true, only allows comments on existing posts and comments
+const STRICT_BASE_PROPERTY: &str = "comments_require_existing_base";
+const COMMENTABLE_ENTRY_TYPES: &[&str] = &["post", COMMENT_ENTRY_TYPE];
+
+fn strict_base_mode() -> bool {
+    hdk::property(STRICT_BASE_PROPERTY)
+        .ok()
+        .and_then(|value| serde_json::from_str::<bool>(&String::from(value)).ok())
+        .unwrap_or(false)
+}
+
+fn validate_base(base: &str) -> Result<(), String> {
+    if !strict_base_mode() {
+        return Ok(());
+    }
+    match hdk::get_entry(&Address::from(base)) {
+        Ok(Some(Entry::App(entry_type, _)))
+            if COMMENTABLE_ENTRY_TYPES.contains(&String::from(entry_type).as_str()) =>
+        {
+            Ok(())
+        }
+        _ => Err(format!(
+            "Comment base must be the address of an existing post or comment: {}",
+            base
+        )),
+    }
+}
+
+fn validate_text(text: &str) -> Result<(), String> {
+    if text.trim().is_empty() {
+        return Err("Comment text cannot be empty".into());
+    }
+    if text.chars().count() > MAX_COMMENT_LENGTH {
+        return Err(format!(
+            "Comment text cannot be longer than {} characters",
+            MAX_COMMENT_LENGTH
+        ));
+    }
+    Ok(())
+}
+
+// replies live in the same base as the comment they reply to
+fn validate_parent(comment: &Comment) -> Result<(), String> {
+    match &comment.parent {
+        Some(parent) => {
+            let parent = utils::get_as_type::<Comment>(parent.clone())
+                .map_err(|_| String::from("Could not load the comment this replies to"))?;
+            if parent.base == comment.base {
+                Ok(())
+            } else {
+                Err("A reply must have the same base as its parent".into())
+            }
+        }
+        None => Ok(()),
+    }
+}
+
+// A reply link goes from the parent to the reply and is made by the reply's creator.
+// Removed replies are emptied rather than unlinked, so the links are never removed
+fn validate_reply_link(validation_data: LinkValidationData) -> Result<(), String> {
+    let (link, validation_data) = match validation_data {
+        LinkValidationData::LinkAdd {
+            link,
+            validation_data,
+        } => (link, validation_data),
+        LinkValidationData::LinkRemove { .. } => {
+            return Err("Replies cannot be unlinked from their parent".into())
+        }
+    };
+    let link = link.link();
+    let reply = utils::get_as_type::<Comment>(link.target().clone())
+        .map_err(|_| String::from("Could not load the reply"))?;
+    if reply.parent.as_ref() != Some(link.base()) {
+        return Err("Reply links must point from the parent of the reply".into());
+    }
+    if validation_data.sources().contains(&reply.creator) {
+        Ok(())
+    } else {
+        Err("Only the creator of a reply can link it to its parent".into())
+    }
+}
+
+// A removal empties a comment. It is made by the comment's creator, or by a moderator of the
+// community named in it
+fn validate_removal(comment: &Comment, sources: &[Address]) -> Result<(), String> {
+    let removal = match &comment.removed {
+        Some(removal) => removal,
+        None => return Ok(()),
+    };
+    if !comment.text.is_empty() || !comment.attachments.is_empty() {
+        return Err("A removed comment cannot keep its text or attachments".into());
+    }
+    if !sources.contains(&removal.by) {
+        return Err("A removal must be committed by the agent it names".into());
+    }
+    let allowed = match &removal.community {
+        None => removal.by == comment.creator,
+        Some(community) => moderation::can_moderate(&comment.base, community, &removal.by)
+            .map_err(|_| String::from("Could not check the moderator of the comment"))?,
+    };
+    if allowed {
+        Ok(())
+    } else {
+        Err("Only the creator or a moderator can delete a comment".into())
+    }
+}
+
+// A comment exactly as it was committed, so every validator sees the same version
+fn comment_version(address: &Address) -> Result<Comment, String> {
+    match hdk::get_entry_initial(address) {
+        Ok(Some(Entry::App(entry_type, content)))
+            if String::from(entry_type) == COMMENT_ENTRY_TYPE =>
+        {
+            Comment::try_from(content).map_err(|_| String::from("Could not read the comment"))
+        }
+        _ => Err("Could not load the comment".into()),
+    }
+}
+
+// A link from a base to a comment is made by the comment's creator, tagged with its kind and timestamp.
+// A removed tag names the removed version, and the link is made by whoever removed the comment.
+// The old link is removed by the creator or by the agent who removed the comment
+fn validate_comment_link(validation_data: LinkValidationData) -> Result<(), String> {
+    let (link, sources, adding) = match validation_data {
+        LinkValidationData::LinkAdd {
+            link,
+            validation_data,
+        } => (link, validation_data.sources(), true),
+        LinkValidationData::LinkRemove {
+            link,
+            validation_data,
+        } => (link, validation_data.sources(), false),
+    };
+    let link = link.link();
+    let comment = comment_version(link.target())?;
+    if base_address(comment.base.clone()).ok().as_ref() != Some(link.base()) {
+        return Err("Comment links must point from the base of the comment".into());
+    }
+    if !adding {
+        if sources.contains(&comment.creator) {
+            return Ok(());
+        }
+        return match utils::get_as_type::<Comment>(link.target().clone()) {
+            Ok(Comment {
+                removed: Some(removal),
+                ..
+            }) if sources.contains(&removal.by) => Ok(()),
+            _ => Err("Only the creator or the remover of a comment can unlink it".into()),
+        };
+    }
+
+    let tag = link.tag().to_string();
+    let mut parts = tag.splitn(2, REMOVAL_TAG_SEPARATOR);
+    let listed = parts.next().unwrap_or_default();
+    match parts.next() {
+        None => {
+            if listed != base_link_tag(&comment) {
+                return Err("The tag does not match the comment".into());
+            }
+            if sources.contains(&comment.creator) {
+                Ok(())
+            } else {
+                Err("Only the creator of a comment can link it to its base".into())
+            }
+        }
+        Some(removed_version) => {
+            let removed = comment_version(&Address::from(removed_version))?;
+            let removal = removed
+                .removed
+                .as_ref()
+                .ok_or_else(|| String::from("A removed tag must name a removed version"))?;
+            // a removed version passed validation as a Modify, which keeps these unchanged
+            if removed.creator != comment.creator
+                || removed.base != comment.base
+                || removed.parent != comment.parent
+                || removed.timestamp != comment.timestamp
+            {
+                return Err("A removed tag must name a version of this comment".into());
+            }
+            if listed != base_link_tag(&removed) {
+                return Err("The tag does not match the comment".into());
+            }
+            if sources.contains(&removal.by) {
+                Ok(())
+            } else {
+                Err("Only the agent who removed a comment can retag it".into())
+            }
+        }
+    }
 }
 
 // Entry definition
@@ -113,9 +820,75 @@ pub fn comment_def() -> ValidatingEntryType {
         validation_package: || {
             hdk::ValidationPackageDefinition::Entry
         },
-        validation: | _validation_data: hdk::EntryValidationData<Comment>| {
-            Ok(())
-        }
+        validation: | validation_data: hdk::EntryValidationData<Comment>| {
+            match validation_data {
+                EntryValidationData::Create{ entry, validation_data } => {
+                    if !validation_data.sources().contains(&entry.creator) {
+                        return Err("The creator of a comment must be the agent committing it".into());
+                    }
+                    if entry.removed.is_some() {
+                        return Err("A comment cannot be created removed".into());
+                    }
+                    validate_text(&entry.text)?;
+                    validate_attachments(&entry.attachments)?;
+                    validate_base(&entry.base)?;
+                    validate_parent(&entry)
+                },
+                EntryValidationData::Modify{ new_entry, old_entry, validation_data, .. } => {
+                    if old_entry.removed.is_some() {
+                        return Err("A removed comment cannot be changed".into());
+                    }
+                    if new_entry.base != old_entry.base
+                        || new_entry.creator != old_entry.creator
+                        || new_entry.parent != old_entry.parent
+                        || new_entry.timestamp != old_entry.timestamp
+                    {
+                        return Err("Only the text of a comment can be edited".into());
+                    }
+                    let sources = validation_data.sources();
+                    if new_entry.removed.is_some() {
+                        return validate_removal(&new_entry, &sources);
+                    }
+                    if !sources.contains(&old_entry.creator) {
+                        return Err("Only the creator of a comment can edit it".into());
+                    }
+                    if new_entry.attachments != old_entry.attachments {
+                        return Err("Only the text of a comment can be edited".into());
+                    }
+                    validate_text(&new_entry.text)
+                },
+                // moderators remove comments with a Modify, so only the creator deletes
+                EntryValidationData::Delete{ old_entry, validation_data, .. } => {
+                    if validation_data.sources().contains(&old_entry.creator) {
+                        Ok(())
+                    } else {
+                        Err("Only the creator of a comment can delete it".into())
+                    }
+                }
+            }
+        },
+        links: [
+            to!(
+                COMMENT_ENTRY_TYPE,
+                link_type: REPLY_LINK_TYPE,
+                validation_package: || {
+                    hdk::ValidationPackageDefinition::Entry
+                },
+                validation: | validation_data: hdk::LinkValidationData| {
+                    validate_reply_link(validation_data)
+                }
+            ),
+            from!(
+                "%agent_id",
+                link_type: MENTION_LINK_TYPE,
+                validation_package: || {
+                    hdk::ValidationPackageDefinition::Entry
+                },
+                validation: | validation_data: hdk::LinkValidationData| {
+                    validate_mention_link(validation_data)
+                }
+            )
+        ]
     )
 }
 
@@ -127,8 +900,11 @@ pub fn base_def() -> ValidatingEntryType {
         validation_package: || {
             hdk::ValidationPackageDefinition::Entry
         },
-        validation: | _validation_data: hdk::EntryValidationData<Base>| {
-            Ok(())
+        validation: | validation_data: hdk::EntryValidationData<Base>| {
+            match validation_data {
+                EntryValidationData::Create{ entry, .. } => validate_base(&entry),
+                _ => Err("Bases cannot be updated or deleted".into()),
+            }
         },
         links: [
             to!(
@@ -137,8 +913,8 @@ pub fn base_def() -> ValidatingEntryType {
                 validation_package: || {
                     hdk::ValidationPackageDefinition::Entry
                 },
-                validation: | _validation_data: hdk::LinkValidationData| {
-                    Ok(())
+                validation: | validation_data: hdk::LinkValidationData| {
+                    validate_comment_link(validation_data)
                 }
             )
         ]
@@ -8,6 +8,7 @@ extern crate serde_json;
 extern crate holochain_json_derive;
 
 mod comments;
+mod moderation;
 
 use hdk::{
     error::ZomeApiResult,
@@ -39,7 +40,7 @@ define_zome! {
 
     functions: [
         create: {
-            inputs: |base: String, text: String, timestamp: Iso8601|,
+            inputs: |base: String, text: String, timestamp: Iso8601, parent: Option<Address>, attachments: Option<Vec<Address>>|,
             outputs: |result: ZomeApiResult<comments::CommentWithAddress>|,
             handler: comments::create
         }
@@ -48,18 +49,48 @@ define_zome! {
             outputs: |result: ZomeApiResult<comments::CommentWithAddress>|,
             handler: comments::get
         }
+        update: {
+            inputs: |address: Address, text: String, timestamp: Iso8601|,
+            outputs: |result: ZomeApiResult<comments::CommentWithAddress>|,
+            handler: comments::update
+        }
+        delete: {
+            inputs: |address: Address|,
+            outputs: |result: ZomeApiResult<Address>|,
+            handler: comments::delete
+        }
         all_for_base: {
-            inputs: |base: String|,
-            outputs: |result: ZomeApiResult<Vec<comments::CommentWithAddress>>|,
+            inputs: |base: String, since: Option<Address>, before: Option<Address>, limit: Option<usize>|,
+            outputs: |result: ZomeApiResult<comments::GetCommentsResult>|,
             handler: comments::all_for_base
         }
+        count_for_bases: {
+            inputs: |bases: Vec<String>|,
+            outputs: |result: ZomeApiResult<Vec<comments::CommentCount>>|,
+            handler: comments::count_for_bases
+        }
+        thread_for_base: {
+            inputs: |base: String, depth: Option<usize>, limit: Option<usize>, after: Option<Address>|,
+            outputs: |result: ZomeApiResult<comments::CommentThread>|,
+            handler: comments::thread_for_base
+        }
+        replies: {
+            inputs: |parent: Address, depth: Option<usize>, limit: Option<usize>, after: Option<Address>|,
+            outputs: |result: ZomeApiResult<comments::CommentThread>|,
+            handler: comments::replies
+        }
     ]
 
     traits: {
         hc_public [
             create,
             get,
-            all_for_base
+            update,
+            delete,
+            all_for_base,
+            count_for_bases,
+            thread_for_base,
+            replies
         ]
     }
 }
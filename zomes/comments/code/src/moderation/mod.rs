@@ -0,0 +1,103 @@
+/**
+ * Moderator lookups for comments made on posts in a community.
+ *
+ * These read the entries of the posts and communities zomes directly rather than calling into them.
+ * `can_moderate` only uses get_entry on addresses worked out from the comment, so it gives the same
+ * answer to every validator and can be used from validation callbacks.
+ */
+use hdk::{
+    error::ZomeApiResult, holochain_core_types::entry::Entry,
+    holochain_persistence_api::cas::content::Address,
+};
+use hdk_helpers::{
+    communities::{community_address_for_slug, moderator_grant_address, COMMUNITY_ENTRY_TYPE},
+    posts::{PostBase, POST_ENTRY_TYPE},
+};
+
+// the parts of a communities zome Community we need
+#[derive(Deserialize)]
+struct CommunityDetails {
+    slug: String,
+    #[serde(default)]
+    creator: Option<Address>,
+}
+
+fn app_entry(address: &Address) -> ZomeApiResult<Option<(String, String)>> {
+    Ok(match hdk::get_entry(address)? {
+        Some(Entry::App(entry_type, content)) => {
+            Some((String::from(entry_type), String::from(content)))
+        }
+        _ => None,
+    })
+}
+
+fn community_details(community: &Address) -> ZomeApiResult<Option<CommunityDetails>> {
+    Ok(match app_entry(community)? {
+        Some((entry_type, content)) if entry_type == COMMUNITY_ENTRY_TYPE => {
+            serde_json::from_str(&content).ok()
+        }
+        _ => None,
+    })
+}
+
+// the base of the post a comment base is, if it is one
+fn post_base(base: &str) -> ZomeApiResult<Option<String>> {
+    Ok(match app_entry(&Address::from(base))? {
+        Some((entry_type, content)) if entry_type == POST_ENTRY_TYPE => {
+            serde_json::from_str::<PostBase>(&content)
+                .ok()
+                .map(|post| post.base)
+        }
+        _ => None,
+    })
+}
+
+// a community base can be either the address or the slug of the community
+fn community_for_base(base: &str) -> ZomeApiResult<Option<Address>> {
+    let address = Address::from(base);
+    if community_details(&address)?.is_some() {
+        return Ok(Some(address));
+    }
+    community_address_for_slug(base)
+}
+
+// The community a comment base belongs to. Either the base is a community itself or a post in one.
+// Follows slug links, so only for use outside validation
+pub fn community_for_comment_base(base: &str) -> ZomeApiResult<Option<Address>> {
+    if let Some(community) = community_for_base(base)? {
+        return Ok(Some(community));
+    }
+    match post_base(base)? {
+        Some(post_base) => community_for_base(&post_base),
+        None => Ok(None),
+    }
+}
+
+/**
+ * @brief      Whether an agent may moderate comments on a base in the given community
+ *
+ * @param      comment_base    Base of the comment. The community itself (by address or slug) or a post in it
+ *
+ * @param      community       Address of the community, as found by community_for_comment_base
+ *
+ * @return     True if the base is in the community and the agent created it or was granted moderation
+ */
+pub fn can_moderate(
+    comment_base: &str,
+    community: &Address,
+    agent_id: &Address,
+) -> ZomeApiResult<bool> {
+    let details = match community_details(community)? {
+        Some(details) => details,
+        None => return Ok(false),
+    };
+    let in_community = |base: &str| base == community.to_string() || base == details.slug;
+    if !in_community(comment_base) && !post_base(comment_base)?.map_or(false, |b| in_community(&b))
+    {
+        return Ok(false);
+    }
+    if details.creator.as_ref() == Some(agent_id) {
+        return Ok(true);
+    }
+    Ok(hdk::get_entry(&moderator_grant_address(community, agent_id)?)?.is_some())
+}
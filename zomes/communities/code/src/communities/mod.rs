@@ -2,22 +2,38 @@ use hdk::{
     self,
     entry_definition::ValidatingEntryType,
     error::{ZomeApiError, ZomeApiResult},
-    holochain_core_types::{dna::entry_types::Sharing, entry::Entry, link::LinkMatch},
+    holochain_core_types::{
+        dna::entry_types::Sharing, entry::Entry, link::LinkMatch, validation::EntryValidationData,
+    },
     holochain_json_api::{
         error::JsonError,
         json::{JsonString, RawString},
     },
     holochain_persistence_api::cas::content::{Address, AddressableContent},
-    utils,
+    utils, AGENT_ADDRESS,
+};
+pub use hdk_helpers::communities::{
+    CommunityStatus, COMMUNITY_BASE_ENTRY, COMMUNITY_ENTRY_TYPE, COMMUNITY_LINK_TYPE,
+};
+use hdk_helpers::{
+    commit_if_not_in_chain,
+    communities::{community_address_for_slug, community_status},
 };
-use hdk_helpers::commit_if_not_in_chain;
 
 use super::DEFAULT_COMMUNITIES;
+use crate::membership::{self, Role};
 
 #[derive(Serialize, Deserialize, Debug, Clone, DefaultJson)]
 pub struct Community {
     pub name: String,
     pub slug: String,
+    // the agent allowed to archive or delete the community. The default communities have none
+    #[serde(default)]
+    pub creator: Option<Address>,
+    #[serde(default)]
+    pub status: CommunityStatus,
+    #[serde(default)]
+    pub created_at: Option<String>,
 }
 
 impl Community {
@@ -26,8 +42,15 @@ impl Community {
             address,
             name: self.name.clone(),
             slug: self.slug.clone(),
+            creator: self.creator.clone(),
+            status: self.status.clone(),
+            created_at: self.created_at.clone(),
         }
     }
+
+    pub fn is_archived(&self) -> bool {
+        self.status == CommunityStatus::Archived
+    }
 }
 
 impl From<&(&str, &str)> for Community {
@@ -35,6 +58,9 @@ impl From<&(&str, &str)> for Community {
         Community {
             name: tuple.0.to_string(),
             slug: tuple.1.to_string(),
+            creator: None,
+            status: CommunityStatus::Active,
+            created_at: None,
         }
     }
 }
@@ -44,25 +70,20 @@ pub struct CommunityWithAddress {
     pub address: Address,
     pub name: String,
     pub slug: String,
+    pub creator: Option<Address>,
+    pub status: CommunityStatus,
+    pub created_at: Option<String>,
 }
 
 impl From<Community> for CommunityWithAddress {
     fn from(community: Community) -> Self {
         let address = Entry::App(COMMUNITY_ENTRY_TYPE.into(), community.clone().into()).address();
-        CommunityWithAddress {
-            address,
-            name: community.name,
-            slug: community.slug,
-        }
+        community.with_address(address)
     }
 }
 
 pub type Base = RawString;
 
-pub const COMMUNITY_ENTRY_TYPE: &str = "community";
-const COMMUNITY_BASE_ENTRY: &str = "community_base";
-const COMMUNITY_LINK_TYPE: &str = "member_of";
-
 pub fn get(address: Address) -> ZomeApiResult<CommunityWithAddress> {
     utils::get_as_type::<Community>(address.clone())
         .map(|community| community.with_address(address))
@@ -77,29 +98,17 @@ pub fn get_by_slug(slug: String) -> ZomeApiResult<CommunityWithAddress> {
         return Ok(CommunityWithAddress::from(Community::from(t)));
     }
     // otherwise go to the DHT
-    let slug_address = hdk::entry_address(&Entry::App(
-        COMMUNITY_BASE_ENTRY.into(),
-        RawString::from(slug).into(),
+    let community_address = community_address_for_slug(&slug)?.ok_or(ZomeApiError::Internal(
+        "No communities for this slug".into(),
     ))?;
-    let all_communities = hdk::get_links(
-        &slug_address,
-        LinkMatch::Exactly(COMMUNITY_LINK_TYPE.into()),
-        LinkMatch::Any,
-    )?
-    .addresses()
-    .clone();
-    let community_address = all_communities
-        .to_owned()
-        .into_iter()
-        .next()
-        .ok_or(ZomeApiError::Internal(
-            "No communities for this slug".into(),
-        ))
-        .unwrap();
     get(community_address)
 }
 
-pub fn create(name: String, slug: String) -> ZomeApiResult<CommunityWithAddress> {
+pub fn create(
+    name: String,
+    slug: String,
+    created_at: Option<String>,
+) -> ZomeApiResult<CommunityWithAddress> {
     let base_entry = Entry::App(
         COMMUNITY_BASE_ENTRY.into(),
         RawString::from(COMMUNITY_BASE_ENTRY).into(),
@@ -115,6 +124,9 @@ pub fn create(name: String, slug: String) -> ZomeApiResult<CommunityWithAddress>
     let community = Community {
         name: name.clone(),
         slug: slug.clone(),
+        creator: Some(AGENT_ADDRESS.to_string().into()),
+        status: CommunityStatus::Active,
+        created_at,
     };
 
     let community_address = hdk::commit_entry(&Entry::App(
@@ -122,33 +134,159 @@ pub fn create(name: String, slug: String) -> ZomeApiResult<CommunityWithAddress>
         community.clone().into(),
     ))?;
 
-    hdk::link_entries(&base_address, &community_address, COMMUNITY_LINK_TYPE, "")?;
+    hdk::link_entries(
+        &base_address,
+        &community_address,
+        COMMUNITY_LINK_TYPE,
+        &listing_tag(&community),
+    )?;
     hdk::link_entries(&slug_address, &community_address, COMMUNITY_LINK_TYPE, "")?;
+    membership::add_member(&community_address, &AGENT_ADDRESS, Role::Admin)?;
 
     Ok(community.with_address(community_address))
 }
 
-pub fn all() -> ZomeApiResult<Vec<CommunityWithAddress>> {
-    let address = hdk::entry_address(&Entry::App(
+// Only the creator of a community may archive or delete it.
+// Returns the current version of the community if the calling agent is allowed to
+fn get_as_admin(address: &Address) -> ZomeApiResult<Community> {
+    let community = utils::get_as_type::<Community>(address.clone())?;
+    if community.creator != Some(AGENT_ADDRESS.to_string().into()) {
+        return Err(ZomeApiError::Internal(
+            "Only the community admin can do this".into(),
+        ));
+    }
+    Ok(community)
+}
+
+pub fn archive(address: Address) -> ZomeApiResult<CommunityWithAddress> {
+    let community = Community {
+        status: CommunityStatus::Archived,
+        ..get_as_admin(&address)?
+    };
+    hdk::update_entry(
+        Entry::App(COMMUNITY_ENTRY_TYPE.into(), community.clone().into()),
+        &address,
+    )?;
+    relist(&address, Some(&community))?;
+    Ok(community.with_address(address))
+}
+
+pub fn delete(address: Address) -> ZomeApiResult<Address> {
+    let community = get_as_admin(&address)?;
+    let slug_address = hdk::entry_address(&Entry::App(
         COMMUNITY_BASE_ENTRY.into(),
-        RawString::from(COMMUNITY_BASE_ENTRY).into(),
+        RawString::from(community.slug).into(),
     ))?;
+    // removing the slug link is what frees the slug up for a new community
+    relist(&address, None)?;
+    hdk::remove_link(&slug_address, &address, COMMUNITY_LINK_TYPE, "")?;
+    hdk::remove_entry(&address)
+}
+
+/**
+ * @brief      Whether new posts may be made with the given base.
+ *             The base may be a community slug or address. Bases that aren't communities are always open.
+ *             Fails rather than answering if the community can't be loaded
+ */
+pub fn accepts_posts(base: String) -> ZomeApiResult<bool> {
+    Ok(community_status(&base)? != Some(CommunityStatus::Archived))
+}
+
+pub fn base_address() -> ZomeApiResult<Address> {
+    hdk::entry_address(&Entry::App(
+        COMMUNITY_BASE_ENTRY.into(),
+        RawString::from(COMMUNITY_BASE_ENTRY).into(),
+    ))
+}
+
+// the link from the communities base carries the community as its tag, so the directory can sort,
+// search and show communities without loading each one
+fn listing_tag(community: &Community) -> String {
+    serde_json::to_string(community).unwrap_or_default()
+}
+
+// Replace a community's link from the communities base, or just remove it when `community` is None
+fn relist(address: &Address, community: Option<&Community>) -> ZomeApiResult<()> {
+    let base_address = base_address()?;
+    for link in hdk::get_links(
+        &base_address,
+        LinkMatch::Exactly(COMMUNITY_LINK_TYPE),
+        LinkMatch::Any,
+    )?
+    .links()
+    {
+        if link.address == *address {
+            hdk::remove_link(&base_address, address, COMMUNITY_LINK_TYPE, &link.tag)?;
+        }
+    }
+    if let Some(community) = community {
+        hdk::link_entries(
+            &base_address,
+            address,
+            COMMUNITY_LINK_TYPE,
+            &listing_tag(community),
+        )?;
+    }
+    Ok(())
+}
+
+/**
+ * @brief      Every community created through `create`, read from the links of the communities base.
+ *             Does not include the defaults
+ *
+ * @return     The address of each community, with its details when its link carries them.
+ *             Communities listed before links carried details have None
+ */
+pub fn listings() -> ZomeApiResult<Vec<(Address, Option<Community>)>> {
     Ok(hdk::get_links(
-        &address,
-        LinkMatch::Exactly(COMMUNITY_LINK_TYPE.into()),
+        &base_address()?,
+        LinkMatch::Exactly(COMMUNITY_LINK_TYPE),
         LinkMatch::Any,
     )?
-    .addresses()
-    .iter()
-    .map(|address| get(address.to_string().into()).unwrap())
-    .chain(
-        DEFAULT_COMMUNITIES
-            .iter()
-            .map(|t| CommunityWithAddress::from(Community::from(t))),
-    ) // include the defaults also
+    .links()
+    .into_iter()
+    .map(|link| {
+        let community = serde_json::from_str::<Community>(&link.tag).ok();
+        (link.address, community)
+    })
     .collect())
 }
 
+// addresses of every community created through `create`. Does not include the defaults
+pub fn linked_addresses() -> ZomeApiResult<Vec<Address>> {
+    Ok(listings()?
+        .into_iter()
+        .map(|(address, _)| address)
+        .collect())
+}
+
+pub fn defaults() -> impl Iterator<Item = CommunityWithAddress> {
+    DEFAULT_COMMUNITIES
+        .iter()
+        .map(|t| CommunityWithAddress::from(Community::from(t)))
+}
+
+// Fails if a listed community can't be read rather than leaving it out
+pub fn all(include_archived: Option<bool>) -> ZomeApiResult<Vec<CommunityWithAddress>> {
+    let include_archived = include_archived.unwrap_or(false);
+    let communities = linked_addresses()?
+        .into_iter()
+        .map(get)
+        .collect::<ZomeApiResult<Vec<CommunityWithAddress>>>()?;
+    Ok(communities
+        .into_iter()
+        .filter(|community| include_archived || community.status != CommunityStatus::Archived)
+        .chain(defaults()) // include the defaults also
+        .collect())
+}
+
+fn validate_admin(community: &Community, sources: &[Address]) -> Result<(), String> {
+    match &community.creator {
+        Some(creator) if sources.contains(creator) => Ok(()),
+        _ => Err("Only the community admin can modify or delete a community".into()),
+    }
+}
+
 pub fn community_def() -> ValidatingEntryType {
     entry!(
         name: COMMUNITY_ENTRY_TYPE,
@@ -159,9 +297,39 @@ pub fn community_def() -> ValidatingEntryType {
             hdk::ValidationPackageDefinition::Entry
         },
 
-        validation: |_validation_data: hdk::EntryValidationData<Community>| {
-            Ok(())
-        }
+        validation: |validation_data: hdk::EntryValidationData<Community>| {
+            match validation_data {
+                EntryValidationData::Create{ entry, validation_data } => {
+                    if entry.status != CommunityStatus::Active {
+                        return Err("New communities must be active".into());
+                    }
+                    match &entry.creator {
+                        Some(creator) if validation_data.sources().contains(creator) => Ok(()),
+                        Some(_) => Err("Agents can only create communities as themselves".into()),
+                        // the defaults are committed by every agent in init and have no creator
+                        None if DEFAULT_COMMUNITIES.contains(&(entry.name.as_str(), entry.slug.as_str())) => Ok(()),
+                        None => Err("Communities must have a creator".into()),
+                    }
+                },
+                EntryValidationData::Modify{ new_entry, old_entry, validation_data, .. } => {
+                    validate_admin(&old_entry, &validation_data.sources())?;
+                    if new_entry.name != old_entry.name
+                        || new_entry.slug != old_entry.slug
+                        || new_entry.creator != old_entry.creator
+                    {
+                        return Err("Only the status of a community can be changed".into());
+                    }
+                    Ok(())
+                },
+                EntryValidationData::Delete{ old_entry, validation_data, .. } => {
+                    validate_admin(&old_entry, &validation_data.sources())
+                }
+            }
+        },
+
+        links: [
+            membership::member_link_def()
+        ]
     )
 }
 
@@ -186,7 +354,8 @@ pub fn base_def() -> ValidatingEntryType {
                 validation: | _validation_data: hdk::LinkValidationData| {
                     Ok(())
                 }
-            )
+            ),
+            membership::member_index_link_def()
         ]
     )
 }
@@ -0,0 +1,172 @@
+use hdk::{
+    error::{ZomeApiError, ZomeApiResult},
+    holochain_json_api::{error::JsonError, json::JsonString},
+    holochain_persistence_api::cas::content::Address,
+};
+use std::cmp::Ordering;
+
+use crate::communities::{self, CommunityStatus, CommunityWithAddress};
+use crate::membership;
+
+const DEFAULT_PAGE_SIZE: usize = 20;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum SortBy {
+    Name,
+    CreatedAt,
+    MemberCount,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, DefaultJson)]
+pub struct DirectoryEntry {
+    #[serde(flatten)]
+    pub community: CommunityWithAddress,
+    pub member_count: usize,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, DefaultJson)]
+pub struct DirectoryResult {
+    communities: Vec<DirectoryEntry>,
+    more: bool,
+    // communities that are linked but could not be loaded. These are left out rather than failing the call
+    unreadable: Vec<Address>,
+}
+
+fn compare(sort_by: &SortBy, a: &DirectoryEntry, b: &DirectoryEntry) -> Ordering {
+    match sort_by {
+        SortBy::Name => a
+            .community
+            .name
+            .to_lowercase()
+            .cmp(&b.community.name.to_lowercase()),
+        // newest first, with communities that have no creation time at the end
+        SortBy::CreatedAt => match (&a.community.created_at, &b.community.created_at) {
+            (Some(a), Some(b)) => b.cmp(a),
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => Ordering::Equal,
+        },
+        // biggest first
+        SortBy::MemberCount => b.member_count.cmp(&a.member_count),
+    }
+    .then_with(|| a.community.address.to_string().cmp(&b.community.address.to_string()))
+}
+
+fn matches_search(community: &CommunityWithAddress, search: &str) -> bool {
+    let search = search.to_lowercase();
+    community.name.to_lowercase().starts_with(&search)
+        || community.slug.to_lowercase().starts_with(&search)
+}
+
+/**
+ * @brief      List communities one page at a time.
+ *             Communities are sorted and searched using the details on their links from the communities base,
+ *             so only communities listed before links carried details are loaded
+ *
+ * @param      sort_by           Order of the listing. Defaults to sorting by name. Sorting by member count
+ *                               uses the member index, so members who joined before it existed are counted
+ *                               once membership::index_members has been run
+ *
+ * @param      search            Only include communities whose name or slug starts with this (case insensitive)
+ *
+ * @param      after             Address of the last community of the previous page. Omit to get the first page.
+ *                               If that community is no longer listed (e.g. it was archived) the page starts
+ *                               from where it would sort. A cursor that is not a community is an error
+ *
+ * @param      limit             Page size
+ *
+ * @param      include_archived  Archived communities are left out unless this is true
+ *
+ * @return     The page of communities, whether there are more after it, and any linked communities that could not be read
+ */
+pub fn directory(
+    sort_by: Option<SortBy>,
+    search: Option<String>,
+    after: Option<Address>,
+    limit: Option<usize>,
+    include_archived: Option<bool>,
+) -> ZomeApiResult<DirectoryResult> {
+    let sort_by = sort_by.unwrap_or(SortBy::Name);
+    let include_archived = include_archived.unwrap_or(false);
+    let limit = limit.unwrap_or(DEFAULT_PAGE_SIZE);
+
+    let mut unreadable = Vec::new();
+    let mut found = Vec::new();
+    for (address, listed) in communities::listings()? {
+        match listed {
+            Some(community) => found.push(community.with_address(address)),
+            None => match communities::get(address.clone()) {
+                Ok(community) => found.push(community),
+                Err(_) => unreadable.push(address),
+            },
+        }
+    }
+
+    let mut entries = found
+        .into_iter()
+        .chain(communities::defaults())
+        .filter(|community| include_archived || community.status != CommunityStatus::Archived)
+        .filter(|community| {
+            search
+                .as_ref()
+                .map_or(true, |search| matches_search(community, search))
+        })
+        .map(|community| DirectoryEntry {
+            community,
+            member_count: 0,
+        })
+        .collect::<Vec<_>>();
+    let counts = if sort_by == SortBy::MemberCount {
+        membership::indexed_member_counts()?
+    } else {
+        Vec::new()
+    };
+    let indexed_count = |address: &Address| {
+        counts
+            .iter()
+            .find(|(community, _)| community == address)
+            .map_or(0, |(_, count)| *count)
+    };
+    for entry in entries.iter_mut() {
+        entry.member_count = indexed_count(&entry.community.address);
+    }
+    entries.sort_by(|a, b| compare(&sort_by, a, b));
+
+    let start = match after {
+        Some(after) => match entries
+            .iter()
+            .position(|entry| entry.community.address == after)
+        {
+            Some(position) => position + 1,
+            None => {
+                let community = communities::get(after).map_err(|_| {
+                    ZomeApiError::Internal("Cursor does not match any community".into())
+                })?;
+                let cursor = DirectoryEntry {
+                    member_count: indexed_count(&community.address),
+                    community,
+                };
+                entries
+                    .iter()
+                    .position(|entry| compare(&sort_by, entry, &cursor) == Ordering::Greater)
+                    .unwrap_or_else(|| entries.len())
+            }
+        },
+        None => 0,
+    };
+    let more = entries.len() > start + limit;
+    let mut communities: Vec<DirectoryEntry> =
+        entries.into_iter().skip(start).take(limit).collect();
+    if sort_by != SortBy::MemberCount {
+        for entry in communities.iter_mut() {
+            entry.member_count = membership::member_count(&entry.community.address)?;
+        }
+    }
+
+    Ok(DirectoryResult {
+        communities,
+        more,
+        unreadable,
+    })
+}
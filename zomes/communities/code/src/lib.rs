@@ -3,9 +3,11 @@ extern crate hdk;
 extern crate serde;
 #[macro_use]
 extern crate serde_derive;
+#[macro_use]
 extern crate serde_json;
 #[macro_use]
 extern crate holochain_json_derive;
+extern crate chrono;
 
 use hdk::{
     error::ZomeApiResult,
@@ -15,6 +17,9 @@ use hdk::{
 };
 
 mod communities;
+mod directory;
+mod membership;
+mod stats;
 use communities::{Community, COMMUNITY_ENTRY_TYPE};
 
 // I'll put this here for now but really it should live in the .dna.json properties
@@ -27,7 +32,8 @@ const DEFAULT_COMMUNITIES: &[(&str, &str)] = &[
 define_zome! {
     entries: [
         communities::base_def(),
-        communities::community_def()
+        communities::community_def(),
+        membership::moderator_grant_def()
     ]
 
     init: || {{
@@ -69,15 +75,60 @@ define_zome! {
             handler: communities::get_by_slug
         }
         create: {
-            inputs: |name: String, slug: String|,
+            inputs: |name: String, slug: String, created_at: Option<String>|,
             outputs: |result: ZomeApiResult<communities::CommunityWithAddress>|,
             handler: communities::create
         }
         all: {
-            inputs: | |,
+            inputs: |include_archived: Option<bool>|,
             outputs: |result: ZomeApiResult<Vec<communities::CommunityWithAddress>>|,
             handler: communities::all
         }
+        directory: {
+            inputs: |sort_by: Option<directory::SortBy>, search: Option<String>, after: Option<Address>, limit: Option<usize>, include_archived: Option<bool>|,
+            outputs: |result: ZomeApiResult<directory::DirectoryResult>|,
+            handler: directory::directory
+        }
+        join: {
+            inputs: |community_address: Address|,
+            outputs: |result: ZomeApiResult<membership::Member>|,
+            handler: membership::join
+        }
+        add_moderator: {
+            inputs: |community_address: Address, agent_id: Address|,
+            outputs: |result: ZomeApiResult<membership::Member>|,
+            handler: membership::add_moderator
+        }
+        members: {
+            inputs: |community_address: Address, role: Option<membership::Role>|,
+            outputs: |result: ZomeApiResult<Vec<membership::Member>>|,
+            handler: membership::members
+        }
+        index_members: {
+            inputs: |community_address: Address|,
+            outputs: |result: ZomeApiResult<usize>|,
+            handler: membership::index_members
+        }
+        stats: {
+            inputs: |community: Address, now: String, days: Option<u32>|,
+            outputs: |result: ZomeApiResult<stats::CommunityStats>|,
+            handler: stats::stats
+        }
+        archive: {
+            inputs: |address: Address|,
+            outputs: |result: ZomeApiResult<communities::CommunityWithAddress>|,
+            handler: communities::archive
+        }
+        delete: {
+            inputs: |address: Address|,
+            outputs: |result: ZomeApiResult<Address>|,
+            handler: communities::delete
+        }
+        accepts_posts: {
+            inputs: |base: String|,
+            outputs: |result: ZomeApiResult<bool>|,
+            handler: communities::accepts_posts
+        }
     ]
 
     traits: {
@@ -85,7 +136,16 @@ define_zome! {
             get,
             create,
             all,
-            get_by_slug
+            get_by_slug,
+            directory,
+            join,
+            add_moderator,
+            members,
+            index_members,
+            stats,
+            archive,
+            delete,
+            accepts_posts
         ]
     }
 }
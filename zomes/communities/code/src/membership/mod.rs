@@ -0,0 +1,308 @@
+use hdk::{
+    self,
+    entry_definition::{ValidatingEntryType, ValidatingLinkDefinition},
+    error::{ZomeApiError, ZomeApiResult},
+    holochain_core_types::{
+        dna::entry_types::Sharing, entry::Entry, link::LinkMatch, validation::EntryValidationData,
+    },
+    holochain_persistence_api::cas::content::Address,
+    utils, AGENT_ADDRESS, LinkValidationData,
+};
+// links from a community to its members (MEMBER_LINK_TYPE) are tagged with the role of the member
+use hdk_helpers::communities::{
+    ModeratorGrant, COMMUNITY_ENTRY_TYPE, MEMBER_LINK_TYPE, MODERATOR_GRANT_ENTRY_TYPE,
+};
+// shared so the people zome can read what members returns
+pub use hdk_helpers::communities::{Member, Role};
+
+use crate::communities::{self, Community};
+
+// communities base -> community, tagged with the address of a member. Lets the directory count the
+// members of every community with a single get_links
+pub const MEMBER_INDEX_LINK_TYPE: &str = "indexes_member";
+
+pub fn add_member(community_address: &Address, agent_id: &Address, role: Role) -> ZomeApiResult<()> {
+    hdk::link_entries(community_address, agent_id, MEMBER_LINK_TYPE, role.as_tag())?;
+    index_member(community_address, agent_id)
+}
+
+fn index_member(community_address: &Address, agent_id: &Address) -> ZomeApiResult<()> {
+    let base_address = communities::base_address()?;
+    let tag = agent_id.to_string();
+    let indexed = hdk::get_links(
+        &base_address,
+        LinkMatch::Exactly(MEMBER_INDEX_LINK_TYPE),
+        LinkMatch::Exactly(tag.as_str()),
+    )?
+    .addresses()
+    .contains(community_address);
+    if !indexed {
+        hdk::link_entries(
+            &base_address,
+            community_address,
+            MEMBER_INDEX_LINK_TYPE,
+            &tag,
+        )?;
+    }
+    Ok(())
+}
+
+// Add the members of a community who joined before members were indexed to the index.
+// Only the community creator can do this. Returns the number of members
+pub fn index_members(community_address: Address) -> ZomeApiResult<usize> {
+    let community = utils::get_as_type::<Community>(community_address.clone())?;
+    if community.creator != Some(AGENT_ADDRESS.to_string().into()) {
+        return Err(ZomeApiError::Internal(
+            "Only the community admin can index its members".into(),
+        ));
+    }
+    let members = members(community_address.clone(), None)?;
+    for member in &members {
+        index_member(&community_address, &member.agent_id)?;
+    }
+    Ok(members.len())
+}
+
+/**
+ * @brief      The number of members of every community, read from the index on the communities base
+ *
+ * @return     The address of each community with at least one indexed member and its count. Members
+ *             who joined before the index existed are counted once index_members has been run
+ */
+pub fn indexed_member_counts() -> ZomeApiResult<Vec<(Address, usize)>> {
+    let mut indexed: Vec<(String, String)> = hdk::get_links(
+        &communities::base_address()?,
+        LinkMatch::Exactly(MEMBER_INDEX_LINK_TYPE),
+        LinkMatch::Any,
+    )?
+    .links()
+    .into_iter()
+    .map(|link| (link.address.to_string(), link.tag))
+    .collect();
+    indexed.sort();
+    indexed.dedup();
+    let mut counts: Vec<(Address, usize)> = Vec::new();
+    for (community, _) in indexed {
+        let community = Address::from(community);
+        match counts.last_mut() {
+            Some((last, count)) if *last == community => *count += 1,
+            _ => counts.push((community, 1)),
+        }
+    }
+    Ok(counts)
+}
+
+pub fn join(community_address: Address) -> ZomeApiResult<Member> {
+    utils::get_as_type::<Community>(community_address.clone())?;
+    let agent_id = Address::from(AGENT_ADDRESS.to_string());
+    if let Some(existing) = members(community_address.clone(), None)?
+        .into_iter()
+        .find(|member| member.agent_id == agent_id)
+    {
+        return Ok(existing);
+    }
+    add_member(&community_address, &agent_id, Role::Member)?;
+    Ok(Member {
+        agent_id,
+        role: Role::Member,
+    })
+}
+
+// Make an agent a moderator of a community. Only the community creator can do this
+pub fn add_moderator(community_address: Address, agent_id: Address) -> ZomeApiResult<Member> {
+    let community = utils::get_as_type::<Community>(community_address.clone())?;
+    if community.creator != Some(AGENT_ADDRESS.to_string().into()) {
+        return Err(ZomeApiError::Internal(
+            "Only the community admin can add moderators".into(),
+        ));
+    }
+    // the grant is what comment moderation checks, the link is for listing members
+    hdk::commit_entry(&Entry::App(
+        MODERATOR_GRANT_ENTRY_TYPE.into(),
+        ModeratorGrant {
+            community: community_address.clone(),
+            agent_id: agent_id.clone(),
+        }
+        .into(),
+    ))?;
+    add_member(&community_address, &agent_id, Role::Moderator)?;
+    Ok(Member {
+        agent_id,
+        role: Role::Moderator,
+    })
+}
+
+/**
+ * @brief      The members of a community, each once with their highest role. Staff have a role link
+ *             as well as their plain membership link
+ *
+ * @param      role        Only include members whose highest role is this
+ */
+pub fn members(community_address: Address, role: Option<Role>) -> ZomeApiResult<Vec<Member>> {
+    let mut members: Vec<Member> = Vec::new();
+    for link in hdk::get_links(
+        &community_address,
+        LinkMatch::Exactly(MEMBER_LINK_TYPE),
+        LinkMatch::Any,
+    )?
+    .links()
+    {
+        let link_role = match Role::from_tag(&link.tag) {
+            Some(link_role) => link_role,
+            None => continue,
+        };
+        match members
+            .iter_mut()
+            .find(|member| member.agent_id == link.address)
+        {
+            Some(member) => {
+                if link_role.outranks(&member.role) {
+                    member.role = link_role;
+                }
+            }
+            None => members.push(Member {
+                agent_id: link.address,
+                role: link_role,
+            }),
+        }
+    }
+    if let Some(role) = role {
+        members.retain(|member| member.role == role);
+    }
+    Ok(members)
+}
+
+pub fn member_count(community_address: &Address) -> ZomeApiResult<usize> {
+    Ok(members(community_address.clone(), None)?.len())
+}
+
+// Agents may add themselves as plain members. Any other role has to be granted by the community creator.
+// Links can be removed by the member themselves or the community creator
+fn validate_member_link(validation_data: LinkValidationData) -> Result<(), String> {
+    match validation_data {
+        LinkValidationData::LinkAdd {
+            link,
+            validation_data,
+        } => {
+            let link = link.link();
+            let sources = validation_data.sources();
+            match Role::from_tag(link.tag()) {
+                Some(Role::Member) if sources.contains(link.target()) => Ok(()),
+                Some(_) => {
+                    let community = utils::get_as_type::<Community>(link.base().clone())
+                        .map_err(|_| String::from("Membership link must point from a community"))?;
+                    match community.creator {
+                        Some(creator) if sources.contains(&creator) => Ok(()),
+                        _ => Err("Only the community admin can grant roles".into()),
+                    }
+                }
+                None => Err(format!("Unknown community role: {}", link.tag())),
+            }
+        }
+        LinkValidationData::LinkRemove {
+            link,
+            validation_data,
+        } => {
+            let link = link.link();
+            let sources = validation_data.sources();
+            if sources.contains(link.target()) {
+                return Ok(());
+            }
+            let community = utils::get_as_type::<Community>(link.base().clone())
+                .map_err(|_| String::from("Membership link must point from a community"))?;
+            match community.creator {
+                Some(creator) if sources.contains(&creator) => Ok(()),
+                _ => Err("Only the member or the community admin can remove a membership".into()),
+            }
+        }
+    }
+}
+
+// The member named in the tag, or the community creator, may index or unindex a membership
+fn validate_member_index(validation_data: LinkValidationData) -> Result<(), String> {
+    let (link, validation_data) = match validation_data {
+        LinkValidationData::LinkAdd {
+            link,
+            validation_data,
+        } => (link, validation_data),
+        LinkValidationData::LinkRemove {
+            link,
+            validation_data,
+        } => (link, validation_data),
+    };
+    let link = link.link();
+    let sources = validation_data.sources();
+    if sources.contains(&Address::from(link.tag().to_string())) {
+        return Ok(());
+    }
+    let community = utils::get_as_type::<Community>(link.target().clone())
+        .map_err(|_| String::from("Member index links must point to a community"))?;
+    match community.creator {
+        Some(creator) if sources.contains(&creator) => Ok(()),
+        _ => Err("Only the member or the community admin can index a membership".into()),
+    }
+}
+
+pub fn member_index_link_def() -> ValidatingLinkDefinition {
+    to!(
+        COMMUNITY_ENTRY_TYPE,
+        link_type: MEMBER_INDEX_LINK_TYPE,
+
+        validation_package: || {
+            hdk::ValidationPackageDefinition::Entry
+        },
+
+        validation: |validation_data: hdk::LinkValidationData| {
+            validate_member_index(validation_data)
+        }
+    )
+}
+
+pub fn member_link_def() -> ValidatingLinkDefinition {
+    to!(
+        "%agent_id",
+        link_type: MEMBER_LINK_TYPE,
+
+        validation_package: || {
+            hdk::ValidationPackageDefinition::Entry
+        },
+
+        validation: |validation_data: hdk::LinkValidationData| {
+            validate_member_link(validation_data)
+        }
+    )
+}
+
+// only the creator of a community can make or take back moderator grants
+fn validate_grant(grant: &ModeratorGrant, sources: &[Address]) -> Result<(), String> {
+    let community = utils::get_as_type::<Community>(grant.community.clone())
+        .map_err(|_| String::from("Moderator grants must be for a community"))?;
+    match community.creator {
+        Some(creator) if sources.contains(&creator) => Ok(()),
+        _ => Err("Only the community admin can grant moderation".into()),
+    }
+}
+
+pub fn moderator_grant_def() -> ValidatingEntryType {
+    entry!(
+        name: MODERATOR_GRANT_ENTRY_TYPE,
+        description: "Permission for an agent to moderate a community",
+        sharing: Sharing::Public,
+
+        validation_package: || {
+            hdk::ValidationPackageDefinition::Entry
+        },
+
+        validation: |validation_data: hdk::EntryValidationData<ModeratorGrant>| {
+            match validation_data {
+                EntryValidationData::Create{ entry, validation_data } => {
+                    validate_grant(&entry, &validation_data.sources())
+                },
+                EntryValidationData::Modify{ .. } => Err("Moderator grants cannot be changed".into()),
+                EntryValidationData::Delete{ old_entry, validation_data, .. } => {
+                    validate_grant(&old_entry, &validation_data.sources())
+                }
+            }
+        }
+    )
+}
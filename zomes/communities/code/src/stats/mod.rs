@@ -0,0 +1,59 @@
+use chrono::{DateTime, Duration};
+use hdk::{
+    error::{ZomeApiError, ZomeApiResult},
+    holochain_persistence_api::cas::content::Address,
+};
+use hdk_helpers::call_zome;
+// shared so the people zome can read it
+pub use hdk_helpers::communities::CommunityStats;
+
+use crate::communities;
+use crate::membership;
+
+const DEFAULT_ACTIVITY_DAYS: u32 = 30;
+
+// mirrors posts::post::PostActivity
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct PostActivity {
+    post_count: usize,
+    recent_post_count: usize,
+    active_posters: Vec<Address>,
+}
+
+/**
+ * @brief      Summary numbers for a community dashboard.
+ *             Everything is read from link indexes (membership links and the posts zome's base links)
+ *             so no posts are loaded. Posts made before the posts zome kept its base links are only
+ *             counted once posts::index_base has been run for the community's slug and address
+ *
+ * @param      community   Address of the community
+ *
+ * @param      now         The current time as an RFC3339 timestamp
+ *
+ * @param      days        Size of the window, ending at now, for the recent post count and active posters. Defaults to 30
+ */
+pub fn stats(community: Address, now: String, days: Option<u32>) -> ZomeApiResult<CommunityStats> {
+    let days = days.unwrap_or(DEFAULT_ACTIVITY_DAYS);
+    let community_with_address = communities::get(community.clone())?;
+    let now = DateTime::parse_from_rfc3339(&now)
+        .map_err(|_| ZomeApiError::Internal("now must be an RFC3339 timestamp".into()))?;
+    let since = now - Duration::days(days.into());
+
+    // posts for a community can be made against either its slug or its address
+    let activity: PostActivity = call_zome(
+        "posts",
+        "activity",
+        json!({
+            "bases": [community_with_address.slug, community.to_string()],
+            "since": since.to_rfc3339(),
+        }),
+    )?;
+
+    Ok(CommunityStats {
+        member_count: membership::member_count(&community)?,
+        post_count: activity.post_count,
+        recent_post_count: activity.recent_post_count,
+        active_posters: activity.active_posters,
+        days,
+    })
+}
@@ -0,0 +1,180 @@
+use hdk::{
+    self,
+    entry_definition::ValidatingEntryType,
+    error::{ZomeApiError, ZomeApiResult},
+    holochain_core_types::{
+        dna::entry_types::Sharing, entry::Entry, validation::EntryValidationData,
+    },
+    holochain_json_api::{error::JsonError, json::JsonString},
+    holochain_persistence_api::cas::content::Address,
+    utils,
+};
+use hdk_helpers::files::{
+    check_manifest, content_hash, FileManifest, FILE_CHUNK_ENTRY_TYPE, FILE_MANIFEST_ENTRY_TYPE,
+    MAX_CHUNK_SIZE, MAX_FILE_SIZE,
+};
+
+// a piece of a file. The same data always gives the same chunk, so files share identical chunks
+#[derive(Serialize, Deserialize, Debug, Clone, DefaultJson)]
+pub struct FileChunk {
+    // base64
+    pub data: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, DefaultJson)]
+pub struct FileWithAddress {
+    pub address: Address,
+    pub name: String,
+    pub mime_type: String,
+    pub size: usize,
+    pub hash: String,
+    pub chunks: Vec<Address>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, DefaultJson)]
+pub struct FileData {
+    pub file: FileWithAddress,
+    // base64 contents of the whole file
+    pub data: String,
+}
+
+fn with_address(manifest: FileManifest, address: Address) -> FileWithAddress {
+    FileWithAddress {
+        address,
+        name: manifest.name,
+        mime_type: manifest.mime_type,
+        size: manifest.size,
+        hash: manifest.hash,
+        chunks: manifest.chunks,
+    }
+}
+
+fn decode(data: &str) -> Result<Vec<u8>, String> {
+    base64::decode(data).map_err(|_| String::from("File data must be base64 encoded"))
+}
+
+fn load_chunk(address: &Address) -> ZomeApiResult<Vec<u8>> {
+    let chunk = utils::get_as_type::<FileChunk>(address.clone())?;
+    decode(&chunk.data).map_err(ZomeApiError::Internal)
+}
+
+/**
+ * @brief      Store a file so it can be attached to messages, posts and comments
+ *
+ * @param      name        The file name, e.g. "minutes.pdf"
+ *
+ * @param      mime_type   e.g. "application/pdf"
+ *
+ * @param      data        The contents, base64 encoded
+ *
+ * @return     The manifest of the stored file. Its address is what gets attached
+ */
+pub fn store(name: String, mime_type: String, data: String) -> ZomeApiResult<FileWithAddress> {
+    let bytes = decode(&data).map_err(ZomeApiError::Internal)?;
+    if bytes.len() > MAX_FILE_SIZE {
+        return Err(ZomeApiError::Internal(format!(
+            "Files can be at most {} bytes",
+            MAX_FILE_SIZE
+        )));
+    }
+    let chunks = bytes
+        .chunks(MAX_CHUNK_SIZE)
+        .map(|chunk| {
+            hdk::commit_entry(&Entry::App(
+                FILE_CHUNK_ENTRY_TYPE.into(),
+                FileChunk {
+                    data: base64::encode(chunk),
+                }
+                .into(),
+            ))
+        })
+        .collect::<ZomeApiResult<Vec<Address>>>()?;
+    let manifest = FileManifest {
+        name,
+        mime_type,
+        size: bytes.len(),
+        hash: content_hash(&bytes),
+        chunks,
+    };
+    check_manifest(&manifest).map_err(ZomeApiError::Internal)?;
+    let address = hdk::commit_entry(&Entry::App(
+        FILE_MANIFEST_ENTRY_TYPE.into(),
+        manifest.clone().into(),
+    ))?;
+    Ok(with_address(manifest, address))
+}
+
+// The name, type, size and chunks of a file, without its contents
+pub fn get(address: Address) -> ZomeApiResult<FileWithAddress> {
+    utils::get_as_type::<FileManifest>(address.clone())
+        .map(|manifest| with_address(manifest, address))
+}
+
+// The base64 contents of one chunk, for fetching large files piece by piece
+pub fn get_chunk(address: Address) -> ZomeApiResult<String> {
+    utils::get_as_type::<FileChunk>(address).map(|chunk| chunk.data)
+}
+
+// A whole file, checked against the hash in its manifest
+pub fn get_data(address: Address) -> ZomeApiResult<FileData> {
+    let file = get(address)?;
+    let mut bytes = Vec::with_capacity(file.size);
+    for chunk in &file.chunks {
+        bytes.extend(load_chunk(chunk)?);
+    }
+    if content_hash(&bytes) != file.hash {
+        return Err(ZomeApiError::Internal(
+            "File contents do not match the manifest".into(),
+        ));
+    }
+    Ok(FileData {
+        file,
+        data: base64::encode(&bytes),
+    })
+}
+
+pub fn chunk_def() -> ValidatingEntryType {
+    entry!(
+        name: FILE_CHUNK_ENTRY_TYPE,
+        description: "A piece of the contents of a file",
+        sharing: Sharing::Public,
+
+        validation_package: || {
+            hdk::ValidationPackageDefinition::Entry
+        },
+
+        validation: |validation_data: hdk::EntryValidationData<FileChunk>| {
+            match validation_data {
+                EntryValidationData::Create{ entry, .. } => {
+                    if decode(&entry.data)?.len() > MAX_CHUNK_SIZE {
+                        Err(format!("File chunks can be at most {} bytes", MAX_CHUNK_SIZE))
+                    } else {
+                        Ok(())
+                    }
+                },
+                _ => Err("File chunks cannot be updated or deleted".into()),
+            }
+        }
+    )
+}
+
+pub fn manifest_def() -> ValidatingEntryType {
+    entry!(
+        name: FILE_MANIFEST_ENTRY_TYPE,
+        description: "The name, type, size and chunks of a file",
+        sharing: Sharing::Public,
+
+        validation_package: || {
+            hdk::ValidationPackageDefinition::Entry
+        },
+
+        validation: |validation_data: hdk::EntryValidationData<FileManifest>| {
+            match validation_data {
+                // checked from the manifest alone. The chunks are checked against its hash when
+                // the file is read with get_data
+                EntryValidationData::Create{ entry, .. } => check_manifest(&entry),
+                _ => Err("Files cannot be updated or deleted".into()),
+            }
+        }
+    )
+}
@@ -0,0 +1,70 @@
+#[macro_use]
+extern crate hdk;
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+extern crate serde_json;
+#[macro_use]
+extern crate holochain_json_derive;
+
+mod files;
+
+use hdk::{
+    error::ZomeApiResult,
+    holochain_core_types::{agent::AgentId, validation::EntryValidationData},
+    holochain_json_api::{error::JsonError, json::JsonString},
+    holochain_persistence_api::cas::content::Address,
+};
+
+define_zome! {
+    entries: [
+        files::chunk_def(),
+        files::manifest_def()
+    ]
+
+    init: || { Ok(()) }
+
+    validate_agent: |validation_data : EntryValidationData::<AgentId>| {{
+         if let EntryValidationData::Create{entry, ..} = validation_data {
+             let agent = entry as AgentId;
+             if agent.nick == "reject_agent::app" {
+                 Err("This agent will always be rejected".into())
+             } else {
+                 Ok(())
+             }
+         } else {
+             Err("Cannot update or delete an agent at this time".into())
+         }
+     }}
+
+    functions: [
+        store: {
+            inputs: |name: String, mime_type: String, data: String|,
+            outputs: |result: ZomeApiResult<files::FileWithAddress>|,
+            handler: files::store
+        }
+        get: {
+            inputs: |address: Address|,
+            outputs: |result: ZomeApiResult<files::FileWithAddress>|,
+            handler: files::get
+        }
+        get_chunk: {
+            inputs: |address: Address|,
+            outputs: |result: ZomeApiResult<String>|,
+            handler: files::get_chunk
+        }
+        get_data: {
+            inputs: |address: Address|,
+            outputs: |result: ZomeApiResult<files::FileData>|,
+            handler: files::get_data
+        }
+    ]
+    traits: {
+        hc_public [
+            store,
+            get,
+            get_chunk,
+            get_data
+        ]
+    }
+}
@@ -0,0 +1,154 @@
+use hdk::{
+    self,
+    entry_definition::ValidatingEntryType,
+    error::{ZomeApiError, ZomeApiResult},
+    holochain_core_types::{dna::entry_types::Sharing, entry::Entry, time::Timeout},
+    holochain_json_api::{error::JsonError, json::JsonString},
+    holochain_persistence_api::cas::content::{Address, AddressableContent},
+    prelude::{QueryArgsOptions, QueryResult},
+    AGENT_ADDRESS,
+};
+use hdk_helpers::commit_if_not_in_chain;
+use std::convert::TryFrom;
+
+use crate::message::{MessageEntry, MESSAGE_ENTRY_TYPE};
+use crate::thread::is_participant;
+
+// private copies of messages sent straight to this agent. Private entries can't be taken back off the
+// chain, so they stay. Once the message is on the DHT the copy just isn't used any more
+pub const DELIVERED_MESSAGE_ENTRY_TYPE: &str = "delivered_message";
+
+// Sends block, one after the other, so how long to wait for each participant and for all of them
+// together are kept small. Zomes have no clock, so every send is counted as taking its whole timeout.
+// Offline participants and those past the budget get the message from the DHT
+const SEND_TIMEOUT_MS: usize = 1000;
+const DELIVERY_BUDGET_MS: usize = 4000;
+
+#[derive(Serialize, Deserialize, Debug, Clone, DefaultJson)]
+pub struct DeliveredMessage {
+    pub address: Address,
+    pub message: MessageEntry,
+}
+
+/**
+ * @brief      Send a just-committed message straight to the other participants of its thread.
+ *             Best effort: participants who can't be reached are skipped and large threads are only partly
+ *             sent to, since every participant can still find the message on the DHT. Waits at most
+ *             DELIVERY_BUDGET_MS in all
+ */
+pub fn deliver(address: &Address, message: &MessageEntry, participants: &[Address]) {
+    let payload = JsonString::from(DeliveredMessage {
+        address: address.clone(),
+        message: message.clone(),
+    })
+    .to_string();
+    let me = Address::from(AGENT_ADDRESS.to_string());
+    let mut remaining_ms = DELIVERY_BUDGET_MS;
+    for participant in participants
+        .iter()
+        .filter(|participant| **participant != me)
+    {
+        if remaining_ms == 0 {
+            break;
+        }
+        let timeout_ms = SEND_TIMEOUT_MS.min(remaining_ms);
+        remaining_ms -= timeout_ms;
+        // a participant who can't be reached doesn't stop the others being sent to
+        if let Err(err) = hdk::send(
+            participant.clone(),
+            payload.clone(),
+            Timeout::new(timeout_ms),
+        ) {
+            let _ = hdk::debug(format!(
+                "Direct delivery to {} failed: {}",
+                participant, err
+            ));
+        }
+    }
+}
+
+fn accept(from: Address, payload: String) -> ZomeApiResult<Address> {
+    let delivered = DeliveredMessage::try_from(JsonString::from_json(&payload))
+        .map_err(|_| ZomeApiError::Internal("Not a delivered message".into()))?;
+    if delivered.message.creator != from {
+        return Err(ZomeApiError::Internal(
+            "Messages can only be delivered by their creator".into(),
+        ));
+    }
+    let entry = Entry::App(MESSAGE_ENTRY_TYPE.into(), delivered.message.clone().into());
+    if entry.address() != delivered.address {
+        return Err(ZomeApiError::Internal(
+            "Message does not match its address".into(),
+        ));
+    }
+    let thread_address = &delivered.message.thread_address;
+    let me = Address::from(AGENT_ADDRESS.to_string());
+    if !is_participant(thread_address, &me)? || !is_participant(thread_address, &from)? {
+        return Err(ZomeApiError::Internal(
+            "Messages can only be delivered between participants of a thread".into(),
+        ));
+    }
+    commit_if_not_in_chain(&Entry::App(
+        DELIVERED_MESSAGE_ENTRY_TYPE.into(),
+        delivered.into(),
+    ))
+}
+
+// The zome's `receive` callback
+pub fn receive(from: Address, payload: String) -> String {
+    match accept(from, payload) {
+        Ok(address) => address.to_string(),
+        Err(err) => err.to_string(),
+    }
+}
+
+fn delivered_messages() -> ZomeApiResult<Vec<DeliveredMessage>> {
+    match hdk::query_result(
+        DELIVERED_MESSAGE_ENTRY_TYPE.into(),
+        QueryArgsOptions {
+            entries: true,
+            ..Default::default()
+        },
+    )? {
+        QueryResult::Entries(entries) => Ok(entries
+            .into_iter()
+            .filter_map(|(_addr, entry)| match entry {
+                Entry::App(_, content) => DeliveredMessage::try_from(content).ok(),
+                _ => None,
+            })
+            .collect()),
+        _ => unreachable!(),
+    }
+}
+
+// The messages delivered to this agent for a thread
+pub fn delivered_to_thread(thread_address: &Address) -> ZomeApiResult<Vec<DeliveredMessage>> {
+    Ok(delivered_messages()?
+        .into_iter()
+        .filter(|delivered| delivered.message.thread_address == *thread_address)
+        .collect())
+}
+
+// This agent's delivered copy of a message, if it has one
+pub fn delivered_copy(address: &Address) -> ZomeApiResult<Option<MessageEntry>> {
+    Ok(delivered_messages()?
+        .into_iter()
+        .find(|delivered| delivered.address == *address)
+        .map(|delivered| delivered.message))
+}
+
+pub fn def() -> ValidatingEntryType {
+    entry!(
+        name: DELIVERED_MESSAGE_ENTRY_TYPE,
+        description: "A message sent directly to this agent. Kept on the agent's own chain",
+        sharing: Sharing::Private,
+
+        validation_package: || {
+            hdk::ValidationPackageDefinition::Entry
+        },
+
+        validation: |_validation_data: hdk::EntryValidationData<DeliveredMessage>| {
+            Ok(())
+        }
+    )
+}
@@ -0,0 +1,200 @@
+use hdk::{
+    self,
+    entry_definition::ValidatingEntryType,
+    error::{ZomeApiError, ZomeApiResult},
+    holochain_core_types::{
+        dna::entry_types::Sharing, entry::Entry, link::LinkMatch, validation::EntryValidationData,
+    },
+    holochain_json_api::{error::JsonError, json::JsonString},
+    holochain_persistence_api::cas::content::Address,
+    utils, LinkValidationData, AGENT_ADDRESS,
+};
+use hdk_helpers::{
+    commit_if_not_in_chain,
+    crypto::{self, Key, Sealed},
+};
+
+pub const ENCRYPTION_KEY_ENTRY_TYPE: &str = "encryption_key";
+// agent -> their published encryption key
+pub const ENCRYPTION_KEY_LINK_TYPE: &str = "has_encryption_key";
+
+// Signing this gives seed material only the agent can produce, so the secret key never has to be stored
+const KEY_SEED_PAYLOAD: &str = "hylo/messages/encryption-key";
+
+#[derive(Serialize, Deserialize, Debug, Clone, DefaultJson)]
+pub struct EncryptionKey {
+    pub agent_id: Address,
+    pub public_key: String,
+}
+
+fn my_secret() -> ZomeApiResult<Key> {
+    Ok(crypto::secret_from_seed(
+        hdk::sign(KEY_SEED_PAYLOAD)?.as_bytes(),
+    ))
+}
+
+// Publish this agent's public key so others can encrypt messages for them.
+// Safe to call repeatedly
+pub fn publish_key() -> ZomeApiResult<Address> {
+    let agent_id = Address::from(AGENT_ADDRESS.to_string());
+    let entry = key_entry(
+        &agent_id,
+        &crypto::encode_key(&crypto::public_key(&my_secret()?)),
+    );
+    let address = commit_if_not_in_chain(&entry)?;
+    let linked = hdk::get_links(
+        &agent_id,
+        LinkMatch::Exactly(ENCRYPTION_KEY_LINK_TYPE),
+        LinkMatch::Any,
+    )?
+    .addresses()
+    .contains(&address);
+    if !linked {
+        hdk::link_entries(&agent_id, &address, ENCRYPTION_KEY_LINK_TYPE, "")?;
+    }
+    Ok(address)
+}
+
+fn key_entry(agent_id: &Address, public_key: &str) -> Entry {
+    Entry::App(
+        ENCRYPTION_KEY_ENTRY_TYPE.into(),
+        EncryptionKey {
+            agent_id: agent_id.clone(),
+            public_key: public_key.to_string(),
+        }
+        .into(),
+    )
+}
+
+fn public_key_for(agent_id: &Address) -> ZomeApiResult<Key> {
+    utils::get_links_and_load_type::<EncryptionKey>(
+        agent_id,
+        LinkMatch::Exactly(ENCRYPTION_KEY_LINK_TYPE),
+        LinkMatch::Any,
+    )?
+    .into_iter()
+    .find(|key| key.agent_id == *agent_id)
+    .ok_or_else(|| {
+        ZomeApiError::Internal(format!(
+            "{} has not published an encryption key yet",
+            agent_id
+        ))
+    })
+    .and_then(|key| crypto::decode_public_key(&key.public_key).map_err(ZomeApiError::Internal))
+}
+
+// Encrypt text so only the given agents can read it. This agent's own key is always included.
+// Fails, naming them, if any recipient has no published key yet (e.g. it hasn't reached this agent),
+// rather than sending something they could never read
+pub fn seal_for(recipients: &[Address], text: &str, context: &str) -> ZomeApiResult<Sealed> {
+    seal_files_for(recipients, text, &[], context).map(|(sealed, _)| sealed)
+}
+
+// Like seal_for, also encrypting files under the same key so the same agents can read them.
+// Returns the encrypted files in the same order
+pub fn seal_files_for(
+    recipients: &[Address],
+    text: &str,
+    files: &[&[u8]],
+    context: &str,
+) -> ZomeApiResult<(Sealed, Vec<Vec<u8>>)> {
+    let secret = my_secret()?;
+    let me = Address::from(AGENT_ADDRESS.to_string());
+    let mut recipient_keys = vec![crypto::public_key(&secret)];
+    let mut missing = Vec::new();
+    for recipient in recipients.iter().filter(|recipient| **recipient != me) {
+        match public_key_for(recipient) {
+            Ok(key) => recipient_keys.push(key),
+            Err(_) => missing.push(recipient.to_string()),
+        }
+    }
+    if !missing.is_empty() {
+        return Err(ZomeApiError::Internal(format!(
+            "No encryption key found for: {}",
+            missing.join(", ")
+        )));
+    }
+    crypto::seal_with_files(&secret, &recipient_keys, text, files, context.as_bytes())
+        .map_err(ZomeApiError::Internal)
+}
+
+// Encrypt new text for exactly the recipients of an earlier sealed text, so an edit is readable by the
+// same people as the original and no one else
+pub fn reseal(previous: &Sealed, text: &str, context: &str) -> ZomeApiResult<Sealed> {
+    let recipient_keys = previous
+        .recipients
+        .iter()
+        .map(|recipient| crypto::decode_public_key(&recipient.recipient_key))
+        .collect::<Result<Vec<Key>, String>>()
+        .map_err(ZomeApiError::Internal)?;
+    crypto::seal(&my_secret()?, &recipient_keys, text, context.as_bytes())
+        .map_err(ZomeApiError::Internal)
+}
+
+// The key a message was sealed with must be one its creator published. Key entries are addressed by
+// their content, so this is a single get_entry that gives every validator the same answer
+pub fn validate_sender_key(creator: &Address, sealed: &Sealed) -> Result<(), String> {
+    let published = hdk::entry_address(&key_entry(creator, &sealed.sender_key))
+        .and_then(|address| hdk::get_entry(&address));
+    match published {
+        Ok(Some(_)) => Ok(()),
+        _ => Err("Messages must be sealed with a key published by their creator".into()),
+    }
+}
+
+pub fn open(sealed: &Sealed) -> ZomeApiResult<String> {
+    crypto::open(&my_secret()?, sealed).map_err(ZomeApiError::Internal)
+}
+
+// The file at `index` of those sealed with seal_files_for
+pub fn open_file(sealed: &Sealed, index: usize, encrypted: &[u8]) -> ZomeApiResult<Vec<u8>> {
+    crypto::open_file(&my_secret()?, sealed, index, encrypted).map_err(ZomeApiError::Internal)
+}
+
+pub fn def() -> ValidatingEntryType {
+    entry!(
+        name: ENCRYPTION_KEY_ENTRY_TYPE,
+        description: "The public key others use to encrypt messages for an agent",
+        sharing: Sharing::Public,
+
+        validation_package: || {
+            hdk::ValidationPackageDefinition::Entry
+        },
+
+        validation: |validation_data: hdk::EntryValidationData<EncryptionKey>| {
+            match validation_data {
+                EntryValidationData::Create{ entry, validation_data } => {
+                    if !validation_data.sources().contains(&entry.agent_id) {
+                        return Err("Agents can only publish their own encryption key".into());
+                    }
+                    crypto::decode_public_key(&entry.public_key).map(|_| ())
+                },
+                _ => Err("Encryption keys cannot be updated or deleted".into()),
+            }
+        },
+
+        links: [
+            from!(
+                "%agent_id",
+                link_type: ENCRYPTION_KEY_LINK_TYPE,
+
+                validation_package: || {
+                    hdk::ValidationPackageDefinition::Entry
+                },
+
+                validation: |validation_data: hdk::LinkValidationData| {
+                    match validation_data {
+                        LinkValidationData::LinkAdd { link, validation_data } => {
+                            if validation_data.sources().contains(link.link().base()) {
+                                Ok(())
+                            } else {
+                                Err("Agents can only link their own encryption key".into())
+                            }
+                        },
+                        _ => Err("Encryption key links cannot be removed".into()),
+                    }
+                }
+            )
+        ]
+    )
+}
@@ -3,6 +3,7 @@ extern crate hdk;
 extern crate serde;
 #[macro_use]
 extern crate serde_derive;
+#[macro_use]
 extern crate serde_json;
 #[macro_use]
 extern crate holochain_json_derive;
@@ -14,16 +15,27 @@ use hdk::{
     holochain_persistence_api::cas::content::Address,
 };
 
+mod delivery;
+mod encryption;
 mod message;
+mod receipt;
+mod summary;
 mod thread;
 
 define_zome! {
     entries: [
         message::def(),
-        thread::def()
+        thread::def(),
+        receipt::def(),
+        encryption::def(),
+        delivery::def()
     ]
 
-    init: || { Ok(()) }
+    init: || {{
+        // others need our key before they can send us anything
+        encryption::publish_key()?;
+        Ok(())
+    }}
 
     validate_agent: |validation_data : EntryValidationData::<AgentId>| {{
          if let EntryValidationData::Create{entry, ..} = validation_data {
@@ -38,10 +50,14 @@ define_zome! {
          }
      }}
 
+    receive: |from, payload| {
+        delivery::receive(from, payload)
+    }
+
     functions: [
         // message functions
         create: {
-            inputs: |thread_address: Address, text: String, timestamp: String|,
+            inputs: |thread_address: Address, text: String, timestamp: String, reply_to: Option<Address>, attachments: Option<Vec<message::NewAttachment>>|,
             outputs: |result: ZomeApiResult<message::MessageWithAddress>|,
             handler: message::create
         }
@@ -50,17 +66,57 @@ define_zome! {
             outputs: |result: ZomeApiResult<message::MessageWithAddress>|,
             handler: message::get
         }
+        edit: {
+            inputs: |message_addr: Address, text: String, timestamp: String|,
+            outputs: |result: ZomeApiResult<message::MessageWithAddress>|,
+            handler: message::edit
+        }
+        unsend: {
+            inputs: |message_addr: Address, timestamp: String|,
+            outputs: |result: ZomeApiResult<Address>|,
+            handler: message::unsend
+        }
+        get_attachment: {
+            inputs: |message_addr: Address, attachment: Address|,
+            outputs: |result: ZomeApiResult<message::AttachmentData>|,
+            handler: message::get_attachment
+        }
         // thread functions
         get_threads: {
             inputs: | |,
             outputs: |result: ZomeApiResult<Vec<Address>>|,
             handler: thread::get_threads
         }
+        get_threads_with_details: {
+            inputs: | |,
+            outputs: |result: ZomeApiResult<Vec<thread::ThreadWithAddress>>|,
+            handler: thread::get_threads_with_details
+        }
         create_thread: {
-            inputs: |participant_ids: Vec<String>|,
+            inputs: |participant_ids: Vec<String>, name: Option<String>, image_url: Option<String>, created_at: Option<String>|,
             outputs: |result: ZomeApiResult<Address>|,
             handler: thread::create_thread
         }
+        find_or_create_thread: {
+            inputs: |participant_ids: Vec<String>|,
+            outputs: |result: ZomeApiResult<Address>|,
+            handler: thread::find_or_create_thread
+        }
+        add_participants: {
+            inputs: |thread_address: Address, participant_ids: Vec<String>|,
+            outputs: |result: ZomeApiResult<Vec<Address>>|,
+            handler: thread::add_participants
+        }
+        leave_thread: {
+            inputs: |thread_address: Address|,
+            outputs: |result: ZomeApiResult<Vec<Address>>|,
+            handler: thread::leave_thread
+        }
+        update_thread_details: {
+            inputs: |thread_address: Address, name: Option<String>, image_url: Option<String>|,
+            outputs: |result: ZomeApiResult<thread::ThreadWithAddress>|,
+            handler: thread::update_thread_details
+        }
         get_participants: {
             inputs: |thread_address: Address|,
             outputs: |result: ZomeApiResult<Vec<Address>>|,
@@ -71,15 +127,53 @@ define_zome! {
             outputs: |result: ZomeApiResult<Vec<message::MessageWithAddress>>|,
             handler: thread::get_thread_messages
         }
+        mark_read: {
+            inputs: |thread_address: Address, up_to_message: Address, share: Option<bool>|,
+            outputs: |result: ZomeApiResult<Address>|,
+            handler: receipt::mark_read
+        }
+        get_read_positions: {
+            inputs: |thread_address: Address|,
+            outputs: |result: ZomeApiResult<Vec<receipt::ReadPosition>>|,
+            handler: receipt::get_read_positions
+        }
+        get_unread_count: {
+            inputs: |thread_address: Address|,
+            outputs: |result: ZomeApiResult<usize>|,
+            handler: receipt::get_unread_count
+        }
+        get_thread_summaries: {
+            inputs: | |,
+            outputs: |result: ZomeApiResult<Vec<summary::ThreadSummary>>|,
+            handler: summary::get_thread_summaries
+        }
+        get_thread_messages_page: {
+            inputs: |thread_address: Address, before: Option<Address>, after: Option<Address>, limit: Option<usize>|,
+            outputs: |result: ZomeApiResult<thread::GetMessagesResult>|,
+            handler: thread::get_thread_messages_page
+        }
     ]
     traits: {
         hc_public [
             create,
             get,
+            edit,
+            unsend,
+            get_attachment,
             get_threads,
+            get_threads_with_details,
             create_thread,
+            find_or_create_thread,
             get_participants,
-            get_thread_messages
+            add_participants,
+            leave_thread,
+            update_thread_details,
+            get_thread_messages,
+            get_thread_messages_page,
+            get_thread_summaries,
+            mark_read,
+            get_read_positions,
+            get_unread_count
         ]
     }
 }
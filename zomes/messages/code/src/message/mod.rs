@@ -1,25 +1,96 @@
 use hdk::{
     self,
     entry_definition::ValidatingEntryType,
-    error::ZomeApiResult,
-    holochain_core_types::{dna::entry_types::Sharing, entry::Entry},
+    error::{ZomeApiError, ZomeApiResult},
+    holochain_core_types::{
+        dna::entry_types::Sharing, entry::Entry, link::LinkMatch, validation::EntryValidationData,
+    },
     holochain_json_api::{error::JsonError, json::JsonString},
-    holochain_persistence_api::cas::content::Address,
-    utils, AGENT_ADDRESS,
+    holochain_persistence_api::cas::content::{Address, AddressableContent},
+    utils, LinkValidationData, AGENT_ADDRESS,
 };
 
-use super::thread::{MESSAGE_LINK_TYPE, THREAD_ENTRY_TYPE};
+use hdk_helpers::{
+    call_zome,
+    crypto::Sealed,
+    files::{check_name_and_type, validate_attachments, MAX_ATTACHMENTS},
+    link_mentions, link_new_mentions, validate_mention_link, MENTION_LINK_TYPE,
+};
+
+use super::delivery;
+use super::encryption;
+use super::thread::{
+    get_thread_participants, is_participant, latest_thread_version, thread_version,
+    MESSAGE_LINK_TYPE, THREAD_ENTRY_TYPE,
+};
+use std::convert::TryFrom;
 
 pub const MESSAGE_ENTRY_TYPE: &str = "message";
 
 pub const MESSAGE_MESSAGE_THREAD_LINK_TYPE: &str = "message_threads";
 
+// how much of a replied-to message is quoted in the reply
+const SNIPPET_LENGTH: usize = 100;
+
+// Attachments are stored with the files zome encrypted, under this name and type so the manifest
+// gives nothing away. Their real name and type are sealed in the message
+const ATTACHMENT_FILE_NAME: &str = "attachment";
+const ATTACHMENT_MIME_TYPE: &str = "application/octet-stream";
+
+// a file to attach to a new message
+#[derive(Serialize, Deserialize, Debug, Clone, DefaultJson)]
+pub struct NewAttachment {
+    pub name: String,
+    pub mime_type: String,
+    // base64
+    pub data: String,
+}
+
+// what is sealed about each attachment, in the order of MessageEntry::attachments
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct AttachmentDetails {
+    name: String,
+    mime_type: String,
+    // in bytes, before encryption
+    size: usize,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, DefaultJson)]
+pub struct MessageAttachment {
+    // address of the encrypted file in the files zome
+    pub address: Address,
+    pub name: String,
+    pub mime_type: String,
+    pub size: usize,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, DefaultJson)]
+pub struct AttachmentData {
+    pub attachment: MessageAttachment,
+    // base64 contents of the whole file
+    pub data: String,
+}
+
+// the part of what the files zome returns that is needed here
+#[derive(Deserialize)]
+struct StoredFile {
+    address: Address,
+}
+
+#[derive(Deserialize)]
+struct StoredFileData {
+    data: String,
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone, DefaultJson)]
 pub struct Message {
     pub timestamp: String,
     pub text: String,
     pub thread_address: Address,
     pub creator: Address,
+    pub reply_to: Option<Address>,
+    pub edited_at: Option<String>,
+    pub attachments: Vec<MessageAttachment>,
 }
 
 impl Message {
@@ -30,48 +101,561 @@ impl Message {
             text: self.text.clone(),
             timestamp: self.timestamp.clone(),
             creator: self.creator.clone(),
+            reply_to: self.reply_to.clone(),
+            quoted: self.reply_to.as_ref().and_then(quote),
+            edited_at: self.edited_at.clone(),
+            attachments: self.attachments.clone(),
         }
     }
 }
 
+// What is actually committed. The text is sealed so only the thread participants can read it.
+// Messages from before encryption have plaintext `text` and no `sealed`
+#[derive(Serialize, Deserialize, Debug, Clone, DefaultJson)]
+pub struct MessageEntry {
+    pub timestamp: String,
+    #[serde(default)]
+    pub text: String,
+    pub thread_address: Address,
+    pub creator: Address,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sealed: Option<Sealed>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reply_to: Option<Address>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub edited_at: Option<String>,
+    // set when the creator unsends the message. The text is gone from this version
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub unsent_at: Option<String>,
+    // encrypted files stored with the files zome
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub attachments: Vec<Address>,
+    // the names, types and sizes of the attachments, sealed for the same people as the text.
+    // The attached files are encrypted under its key. Unlike the text this isn't resealed by edits
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sealed_attachments: Option<Sealed>,
+    // the latest version of the thread when the message was sent, which validation checks the creator
+    // was a participant of. Messages from before this were checked against the latest version
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub thread_version: Option<Address>,
+}
+
+impl MessageEntry {
+    pub fn open(self) -> ZomeApiResult<Message> {
+        if self.unsent_at.is_some() {
+            return Err(ZomeApiError::Internal("Message was unsent".into()));
+        }
+        let text = match &self.sealed {
+            Some(sealed) => encryption::open(sealed)?,
+            None => self.text,
+        };
+        let attachments = match &self.sealed_attachments {
+            Some(sealed) => self
+                .attachments
+                .into_iter()
+                .zip(attachment_details(sealed)?)
+                .map(|(address, details)| MessageAttachment {
+                    address,
+                    name: details.name,
+                    mime_type: details.mime_type,
+                    size: details.size,
+                })
+                .collect(),
+            None => Vec::new(),
+        };
+        Ok(Message {
+            timestamp: self.timestamp,
+            text,
+            thread_address: self.thread_address,
+            creator: self.creator,
+            reply_to: self.reply_to,
+            edited_at: self.edited_at,
+            attachments,
+        })
+    }
+}
+
+fn attachment_details(sealed: &Sealed) -> ZomeApiResult<Vec<AttachmentDetails>> {
+    serde_json::from_str(&encryption::open(sealed)?)
+        .map_err(|_| ZomeApiError::Internal("Could not read the attachments".into()))
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, DefaultJson)]
+pub struct QuotedMessage {
+    pub address: Address,
+    pub creator: Address,
+    pub snippet: String,
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone, DefaultJson)]
 pub struct MessageWithAddress {
-    address: Address,
+    pub address: Address,
     pub timestamp: String,
     pub text: String,
     pub thread_address: Address,
     pub creator: Address,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reply_to: Option<Address>,
+    // the start of the message replied to, if it can still be read
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quoted: Option<QuotedMessage>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub edited_at: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub attachments: Vec<MessageAttachment>,
+}
+
+// The latest version of a message, from the DHT or else a directly delivered copy
+fn load(message_addr: &Address) -> ZomeApiResult<MessageEntry> {
+    match utils::get_as_type::<MessageEntry>(message_addr.clone()) {
+        Ok(entry) => Ok(entry),
+        Err(err) => delivery::delivered_copy(message_addr)?.ok_or(err),
+    }
+}
+
+fn quote(message_addr: &Address) -> Option<QuotedMessage> {
+    let message = load(message_addr).and_then(MessageEntry::open).ok()?;
+    Some(QuotedMessage {
+        address: message_addr.clone(),
+        creator: message.creator,
+        snippet: message.text.chars().take(SNIPPET_LENGTH).collect(),
+    })
+}
+
+// replies must stay within their thread
+fn check_reply_to(thread_address: &Address, reply_to: &Option<Address>) -> ZomeApiResult<()> {
+    if let Some(reply_to) = reply_to {
+        if load(reply_to)?.thread_address != *thread_address {
+            return Err(ZomeApiError::Internal(
+                "Can only reply to a message in the same thread".into(),
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn seal(message: &Message, participants: &[Address], at: &str) -> ZomeApiResult<Sealed> {
+    encryption::seal_for(
+        participants,
+        &message.text,
+        &format!("{}:{}", message.thread_address, at),
+    )
+}
+
+// Encrypt files for the participants and store them with the files zome. Returns the stored
+// attachments and their details sealed for the participants
+fn store_attachments(
+    message: &Message,
+    participants: &[Address],
+    attachments: Vec<NewAttachment>,
+) -> ZomeApiResult<(Vec<MessageAttachment>, Sealed)> {
+    if attachments.len() > MAX_ATTACHMENTS {
+        return Err(ZomeApiError::Internal(format!(
+            "At most {} files can be attached",
+            MAX_ATTACHMENTS
+        )));
+    }
+    let mut details = Vec::new();
+    let mut files = Vec::new();
+    for attachment in attachments {
+        check_name_and_type(&attachment.name, &attachment.mime_type)
+            .map_err(ZomeApiError::Internal)?;
+        let data = base64::decode(&attachment.data)
+            .map_err(|_| ZomeApiError::Internal("Attachment data must be base64 encoded".into()))?;
+        details.push(AttachmentDetails {
+            name: attachment.name,
+            mime_type: attachment.mime_type,
+            size: data.len(),
+        });
+        files.push(data);
+    }
+    let (sealed, encrypted) = encryption::seal_files_for(
+        participants,
+        &serde_json::to_string(&details).map_err(|e| ZomeApiError::Internal(e.to_string()))?,
+        &files.iter().map(Vec::as_slice).collect::<Vec<_>>(),
+        &format!("{}:{}", message.thread_address, message.timestamp),
+    )?;
+    let stored = encrypted
+        .iter()
+        .zip(details)
+        .map(|(file, details)| {
+            let stored: StoredFile = call_zome(
+                "files",
+                "store",
+                json!({
+                    "name": ATTACHMENT_FILE_NAME,
+                    "mime_type": ATTACHMENT_MIME_TYPE,
+                    "data": base64::encode(file),
+                }),
+            )?;
+            Ok(MessageAttachment {
+                address: stored.address,
+                name: details.name,
+                mime_type: details.mime_type,
+                size: details.size,
+            })
+        })
+        .collect::<ZomeApiResult<Vec<_>>>()?;
+    Ok((stored, sealed))
 }
 
+/**
+ * @brief      Post a message in a thread
+ *
+ * @param      reply_to    Address of an earlier message in the same thread this replies to
+ *
+ * @param      attachments Files to send with the message. They are encrypted for the thread participants
+ *                         like the text and stored with the files zome. Read them with get_attachment
+ */
 pub fn create(
     thread_address: Address,
     text: String,
     timestamp: String,
+    reply_to: Option<Address>,
+    attachments: Option<Vec<NewAttachment>>,
 ) -> ZomeApiResult<MessageWithAddress> {
-    let message = Message {
+    if !is_participant(&thread_address, &AGENT_ADDRESS)? {
+        return Err(ZomeApiError::Internal(
+            "Only participants can post in a thread".into(),
+        ));
+    }
+    check_reply_to(&thread_address, &reply_to)?;
+    let mut message = Message {
         text,
         timestamp: timestamp,
         thread_address: thread_address.to_owned(),
         creator: AGENT_ADDRESS.to_string().into(),
+        reply_to,
+        edited_at: None,
+        attachments: Vec::new(),
+    };
+    // the sender is a participant too, so they can read their own messages back
+    encryption::publish_key()?;
+    let participants = get_thread_participants(thread_address.clone())?;
+    let sealed_attachments = match attachments {
+        Some(attachments) if !attachments.is_empty() => {
+            let (stored, sealed) = store_attachments(&message, &participants, attachments)?;
+            message.attachments = stored;
+            Some(sealed)
+        }
+        _ => None,
+    };
+    let stored = MessageEntry {
+        timestamp: message.timestamp.clone(),
+        text: String::new(),
+        thread_address: message.thread_address.clone(),
+        creator: message.creator.clone(),
+        sealed: Some(seal(&message, &participants, &message.timestamp)?),
+        reply_to: message.reply_to.clone(),
+        edited_at: None,
+        unsent_at: None,
+        attachments: message
+            .attachments
+            .iter()
+            .map(|attachment| attachment.address.clone())
+            .collect(),
+        sealed_attachments,
+        thread_version: Some(latest_thread_version(&thread_address)?),
     };
-    let message_entry = Entry::App(MESSAGE_ENTRY_TYPE.into(), message.clone().into());
+    let message_entry = Entry::App(MESSAGE_ENTRY_TYPE.into(), stored.clone().into());
     let message_addr = hdk::commit_entry(&message_entry)?;
+    // the link from the thread is tagged with the timestamp so threads can be paged without loading messages
     utils::link_entries_bidir(
         &message_addr,
         &thread_address,
         MESSAGE_MESSAGE_THREAD_LINK_TYPE,
         MESSAGE_LINK_TYPE,
         "",
-        "",
+        &message.timestamp,
     )?;
+    link_mentions(
+        &message.text,
+        &message_addr,
+        MESSAGE_ENTRY_TYPE,
+        &message.timestamp,
+    );
+    delivery::deliver(&message_addr, &stored, &participants);
     Ok(message.with_address(message_addr))
 }
 
+// When a message was sent. Works for messages this agent can't read and for unsent ones
+pub fn timestamp(message_addr: &Address) -> ZomeApiResult<String> {
+    load(message_addr).map(|entry| entry.timestamp)
+}
+
+// Like timestamp, along with the thread the message was sent in
+pub fn timestamp_and_thread(message_addr: &Address) -> ZomeApiResult<(String, Address)> {
+    load(message_addr).map(|entry| (entry.timestamp, entry.thread_address))
+}
+
+// Fails for agents the message was not encrypted for, and for unsent messages
 pub fn get(message_addr: Address) -> ZomeApiResult<MessageWithAddress> {
-    utils::get_as_type::<Message>(message_addr.clone())
+    load(&message_addr)?
+        .open()
         .map(|message| message.with_address(message_addr))
 }
 
+/**
+ * @brief      Get a file attached to a message, decrypted. Like the text, only the participants the
+ *             message was sent to can read it
+ *
+ * @param      attachment  Address of the attachment, as listed in the message
+ *
+ * @return     The name, type and size of the file and its base64 contents
+ */
+pub fn get_attachment(message_addr: Address, attachment: Address) -> ZomeApiResult<AttachmentData> {
+    let entry = load(&message_addr)?;
+    if entry.unsent_at.is_some() {
+        return Err(ZomeApiError::Internal("Message was unsent".into()));
+    }
+    let (index, sealed) = match (
+        entry
+            .attachments
+            .iter()
+            .position(|address| *address == attachment),
+        &entry.sealed_attachments,
+    ) {
+        (Some(index), Some(sealed)) => (index, sealed),
+        _ => {
+            return Err(ZomeApiError::Internal(
+                "Not an attachment of this message".into(),
+            ))
+        }
+    };
+    let details = attachment_details(sealed)?
+        .into_iter()
+        .nth(index)
+        .ok_or_else(|| ZomeApiError::Internal("Could not read the attachments".into()))?;
+    let stored: StoredFileData = call_zome("files", "get_data", json!({ "address": attachment }))?;
+    let encrypted = base64::decode(&stored.data)
+        .map_err(|_| ZomeApiError::Internal("Could not read the attachment".into()))?;
+    let data = encryption::open_file(sealed, index, &encrypted)?;
+    Ok(AttachmentData {
+        attachment: MessageAttachment {
+            address: attachment,
+            name: details.name,
+            mime_type: details.mime_type,
+            size: details.size,
+        },
+        data: base64::encode(&data),
+    })
+}
+
+// The entry at the end of the message's update chain, which is what updates have to replace
+fn latest_version(message_addr: &Address) -> ZomeApiResult<(Address, MessageEntry)> {
+    let latest = match hdk::get_entry(message_addr)? {
+        Some(entry) => entry,
+        None => return Err(ZomeApiError::Internal("Message not found".into())),
+    };
+    let entry = utils::get_as_type::<MessageEntry>(message_addr.clone())?;
+    if entry.creator != Address::from(AGENT_ADDRESS.to_string()) {
+        return Err(ZomeApiError::Internal(
+            "Only the creator of a message can change it".into(),
+        ));
+    }
+    if entry.unsent_at.is_some() {
+        return Err(ZomeApiError::Internal("Message was unsent".into()));
+    }
+    Ok((latest.address(), entry))
+}
+
+// Change the text of a message. Only the creator can do this
+pub fn edit(
+    message_addr: Address,
+    text: String,
+    timestamp: String,
+) -> ZomeApiResult<MessageWithAddress> {
+    let (latest_addr, entry) = latest_version(&message_addr)?;
+    let previous = entry.clone().open()?;
+    let message = Message {
+        text,
+        edited_at: Some(timestamp.clone()),
+        ..previous.clone()
+    };
+    // sealed for the same people who could read the original. Plaintext messages from before
+    // encryption were readable by anyone, so those are sealed for the thread as it is now
+    let sealed = match &entry.sealed {
+        Some(original) => encryption::reseal(
+            original,
+            &message.text,
+            &format!("{}:{}", message.thread_address, timestamp),
+        )?,
+        None => seal(
+            &message,
+            &get_thread_participants(entry.thread_address.clone())?,
+            &timestamp,
+        )?,
+    };
+    let updated = MessageEntry {
+        sealed: Some(sealed),
+        edited_at: Some(timestamp.clone()),
+        ..entry
+    };
+    hdk::update_entry(
+        Entry::App(MESSAGE_ENTRY_TYPE.into(), updated.into()),
+        &latest_addr,
+    )?;
+    link_new_mentions(
+        &previous.text,
+        &message.text,
+        &message_addr,
+        MESSAGE_ENTRY_TYPE,
+        &timestamp,
+    );
+    Ok(message.with_address(message_addr))
+}
+
+/**
+ * @brief      Take back a message. Its text is replaced with nothing and it is no longer listed in
+ *             its thread. Only the creator can do this
+ */
+pub fn unsend(message_addr: Address, timestamp: String) -> ZomeApiResult<Address> {
+    let (latest_addr, entry) = latest_version(&message_addr)?;
+    let thread_address = entry.thread_address.clone();
+    let retracted = MessageEntry {
+        text: String::new(),
+        sealed: None,
+        attachments: Vec::new(),
+        sealed_attachments: None,
+        unsent_at: Some(timestamp),
+        ..entry
+    };
+    hdk::update_entry(
+        Entry::App(MESSAGE_ENTRY_TYPE.into(), retracted.into()),
+        &latest_addr,
+    )?;
+    hdk::remove_link(
+        &message_addr,
+        &thread_address,
+        MESSAGE_MESSAGE_THREAD_LINK_TYPE,
+        "",
+    )?;
+    // messages from before the links were tagged have an empty tag
+    for link in hdk::get_links(
+        &thread_address,
+        LinkMatch::Exactly(MESSAGE_LINK_TYPE),
+        LinkMatch::Any,
+    )?
+    .links()
+    {
+        if link.address == message_addr {
+            hdk::remove_link(&thread_address, &message_addr, MESSAGE_LINK_TYPE, &link.tag)?;
+        }
+    }
+    Ok(message_addr)
+}
+
+// Only the text of a message may change, by its creator, until it is unsent
+fn validate_change(
+    old_entry: &MessageEntry,
+    new_entry: &MessageEntry,
+    sources: &[Address],
+) -> Result<(), String> {
+    if !sources.contains(&old_entry.creator) {
+        return Err("Only the creator of a message can edit or unsend it".into());
+    }
+    if old_entry.unsent_at.is_some() {
+        return Err("Unsent messages cannot be changed".into());
+    }
+    if new_entry.timestamp != old_entry.timestamp
+        || new_entry.thread_address != old_entry.thread_address
+        || new_entry.creator != old_entry.creator
+        || new_entry.reply_to != old_entry.reply_to
+        || new_entry.thread_version != old_entry.thread_version
+    {
+        return Err("Only the text of a message can be changed".into());
+    }
+    if new_entry.unsent_at.is_some() {
+        if new_entry.sealed.is_some() || !new_entry.text.is_empty() {
+            return Err("Unsent messages cannot keep their text".into());
+        }
+        if !new_entry.attachments.is_empty() || new_entry.sealed_attachments.is_some() {
+            return Err("Unsent messages cannot keep their attachments".into());
+        }
+    } else if new_entry.sealed.is_none() || !new_entry.text.is_empty() {
+        return Err("Message text must be encrypted".into());
+    } else if new_entry.attachments != old_entry.attachments
+        || new_entry.sealed_attachments != old_entry.sealed_attachments
+    {
+        return Err("Only the text of a message can be changed".into());
+    }
+    match &new_entry.sealed {
+        Some(sealed) => encryption::validate_sender_key(&new_entry.creator, sealed),
+        None => Ok(()),
+    }
+}
+
+// A message exactly as it was committed. Its creator, thread and timestamp never change, so every
+// validator sees the same ones
+fn message_version(message_addr: &Address) -> Result<MessageEntry, String> {
+    match hdk::get_entry_initial(message_addr) {
+        Ok(Some(Entry::App(entry_type, content)))
+            if String::from(entry_type) == MESSAGE_ENTRY_TYPE =>
+        {
+            MessageEntry::try_from(content).map_err(|_| String::from("Could not read the message"))
+        }
+        _ => Err("Could not load the message".into()),
+    }
+}
+
+// Links between a message and its thread are made and removed by the message's creator, and only
+// between the message and the thread it names
+fn check_thread_link(
+    message_addr: &Address,
+    thread_address: &Address,
+    sources: &[Address],
+) -> Result<MessageEntry, String> {
+    let message = message_version(message_addr)?;
+    if message.thread_address != *thread_address {
+        return Err("A message can only be linked with its own thread".into());
+    }
+    if !sources.contains(&message.creator) {
+        return Err("Only the creator of a message can link it to its thread".into());
+    }
+    Ok(message)
+}
+
+// thread -> message, tagged with the message timestamp
+pub fn validate_message_link(validation_data: LinkValidationData) -> Result<(), String> {
+    match validation_data {
+        LinkValidationData::LinkAdd {
+            link,
+            validation_data,
+        } => {
+            let link = link.link();
+            let message =
+                check_thread_link(link.target(), link.base(), &validation_data.sources())?;
+            if link.tag() != &message.timestamp {
+                return Err("Message links must be tagged with the message timestamp".into());
+            }
+            Ok(())
+        }
+        LinkValidationData::LinkRemove {
+            link,
+            validation_data,
+        } => {
+            let link = link.link();
+            check_thread_link(link.target(), link.base(), &validation_data.sources()).map(|_| ())
+        }
+    }
+}
+
+// message -> thread
+fn validate_message_thread_link(validation_data: LinkValidationData) -> Result<(), String> {
+    let (link, validation_data) = match validation_data {
+        LinkValidationData::LinkAdd {
+            link,
+            validation_data,
+        } => (link, validation_data),
+        LinkValidationData::LinkRemove {
+            link,
+            validation_data,
+        } => (link, validation_data),
+    };
+    let link = link.link();
+    check_thread_link(link.base(), link.target(), &validation_data.sources()).map(|_| ())
+}
+
 pub fn def() -> ValidatingEntryType {
     entry!(
         name: MESSAGE_ENTRY_TYPE,
@@ -82,8 +666,51 @@ pub fn def() -> ValidatingEntryType {
             hdk::ValidationPackageDefinition::Entry
         },
 
-        validation: |_validation_data: hdk::EntryValidationData<Message>| {
-            Ok(())
+        validation: |validation_data: hdk::EntryValidationData<MessageEntry>| {
+            match validation_data {
+                EntryValidationData::Create{ entry, validation_data } => {
+                    if !validation_data.sources().contains(&entry.creator) {
+                        return Err("The creator of a message must be the agent committing it".into());
+                    }
+                    match &entry.sealed {
+                        Some(sealed) if entry.text.is_empty() => {
+                            encryption::validate_sender_key(&entry.creator, sealed)?
+                        }
+                        _ => return Err("Message text must be encrypted".into()),
+                    }
+                    validate_attachments(&entry.attachments)?;
+                    match &entry.sealed_attachments {
+                        Some(sealed) if !entry.attachments.is_empty() => {
+                            encryption::validate_sender_key(&entry.creator, sealed)?
+                        }
+                        None if entry.attachments.is_empty() => (),
+                        _ => return Err("Attachments must come with their sealed details".into()),
+                    }
+                    // checked against the version of the thread named in the message, so messages stay
+                    // valid after their creator leaves
+                    let participant = match &entry.thread_version {
+                        Some(version) => thread_version(&entry.thread_address, version)?
+                            .participants
+                            .contains(&entry.creator.to_string()),
+                        None => is_participant(&entry.thread_address, &entry.creator)
+                            .map_err(|_| String::from("Could not load the thread of this message"))?,
+                    };
+                    if !participant {
+                        return Err("Only participants can post in a thread".into());
+                    }
+                    match &entry.reply_to {
+                        Some(reply_to) => match utils::get_as_type::<MessageEntry>(reply_to.clone()) {
+                            Ok(replied) if replied.thread_address == entry.thread_address => Ok(()),
+                            _ => Err("A reply must be to a message in the same thread".into()),
+                        },
+                        None => Ok(()),
+                    }
+                },
+                EntryValidationData::Modify{ new_entry, old_entry, validation_data, .. } => {
+                    validate_change(&old_entry, &new_entry, &validation_data.sources())
+                },
+                EntryValidationData::Delete{ .. } => Err("Messages are unsent rather than deleted".into()),
+            }
         },
 
         links: [
@@ -95,8 +722,20 @@ pub fn def() -> ValidatingEntryType {
                     hdk::ValidationPackageDefinition::Entry
                 },
 
-                validation: |_validation_data: hdk::LinkValidationData| {
-                    Ok(())
+                validation: |validation_data: hdk::LinkValidationData| {
+                    validate_message_thread_link(validation_data)
+                }
+            ),
+            from!(
+                "%agent_id",
+                link_type: MENTION_LINK_TYPE,
+
+                validation_package: || {
+                    hdk::ValidationPackageDefinition::Entry
+                },
+
+                validation: |validation_data: hdk::LinkValidationData| {
+                    validate_mention_link(validation_data)
                 }
             )
         ]
@@ -0,0 +1,219 @@
+use hdk::{
+    self,
+    entry_definition::{ValidatingEntryType, ValidatingLinkDefinition},
+    error::{ZomeApiError, ZomeApiResult},
+    holochain_core_types::{dna::entry_types::Sharing, entry::Entry, link::LinkMatch},
+    holochain_json_api::{error::JsonError, json::JsonString},
+    holochain_persistence_api::cas::content::Address,
+    prelude::{QueryArgsOptions, QueryResult},
+    AGENT_ADDRESS, LinkValidationData,
+};
+use hdk_helpers::commit_if_not_in_chain;
+use std::{cmp::Ordering, convert::TryFrom};
+
+use crate::message::{get, MessageWithAddress};
+use crate::thread::{compare_at, compare_messages, get_thread_messages};
+
+// private to the agent's own chain
+pub const READ_MARKER_ENTRY_TYPE: &str = "read_marker";
+// thread -> reader, tagged with the address of the last message they read. Only made when sharing receipts
+pub const READ_RECEIPT_LINK_TYPE: &str = "read_up_to";
+
+#[derive(Serialize, Deserialize, Debug, Clone, DefaultJson)]
+pub struct ReadMarker {
+    pub thread_address: Address,
+    pub message_address: Address,
+    pub message_timestamp: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, DefaultJson)]
+pub struct ReadPosition {
+    pub agent_id: Address,
+    pub message_address: Address,
+}
+
+fn compare_markers(a: &ReadMarker, b: &ReadMarker) -> Ordering {
+    compare_at(
+        (&a.message_timestamp, &a.message_address),
+        (&b.message_timestamp, &b.message_address),
+    )
+}
+
+// the furthest this agent has marked as read in a thread
+fn read_marker(thread_address: &Address) -> ZomeApiResult<Option<ReadMarker>> {
+    match hdk::query_result(
+        READ_MARKER_ENTRY_TYPE.into(),
+        QueryArgsOptions {
+            entries: true,
+            ..Default::default()
+        },
+    )? {
+        QueryResult::Entries(entries) => Ok(entries
+            .into_iter()
+            .filter_map(|(_addr, entry)| match entry {
+                Entry::App(_, content) => ReadMarker::try_from(content).ok(),
+                _ => None,
+            })
+            .filter(|marker| marker.thread_address == *thread_address)
+            .max_by(compare_markers)),
+        _ => unreachable!(),
+    }
+}
+
+/**
+ * @brief      Record that this agent has read a thread up to and including a message.
+ *             Marking an earlier message than one already marked has no effect
+ *
+ * @param      share       Also tell the other participants (for "seen by"). Defaults to false, so
+ *                         nothing is published unless asked for. Unread counts only need the private marker
+ */
+pub fn mark_read(
+    thread_address: Address,
+    up_to_message: Address,
+    share: Option<bool>,
+) -> ZomeApiResult<Address> {
+    let message = get(up_to_message.clone())?;
+    if message.thread_address != thread_address {
+        return Err(ZomeApiError::Internal(
+            "Message is not in this thread".into(),
+        ));
+    }
+    let marker = ReadMarker {
+        thread_address: thread_address.clone(),
+        message_address: up_to_message.clone(),
+        message_timestamp: message.timestamp,
+    };
+    if let Some(existing) = read_marker(&thread_address)? {
+        if compare_markers(&existing, &marker) != Ordering::Less {
+            return Ok(existing.message_address);
+        }
+    }
+    commit_if_not_in_chain(&Entry::App(
+        READ_MARKER_ENTRY_TYPE.into(),
+        marker.into(),
+    ))?;
+
+    if share.unwrap_or(false) {
+        for old_receipt in hdk::get_links(
+            &thread_address,
+            LinkMatch::Exactly(READ_RECEIPT_LINK_TYPE),
+            LinkMatch::Any,
+        )?
+        .links()
+        .into_iter()
+        .filter(|link| link.address == Address::from(AGENT_ADDRESS.to_string()))
+        {
+            hdk::remove_link(
+                &thread_address,
+                &AGENT_ADDRESS,
+                READ_RECEIPT_LINK_TYPE,
+                &old_receipt.tag,
+            )?;
+        }
+        hdk::link_entries(
+            &thread_address,
+            &AGENT_ADDRESS,
+            READ_RECEIPT_LINK_TYPE,
+            &up_to_message.to_string(),
+        )?;
+    }
+    Ok(up_to_message)
+}
+
+// The shared read positions of everyone in the thread who has shared them
+pub fn get_read_positions(thread_address: Address) -> ZomeApiResult<Vec<ReadPosition>> {
+    Ok(hdk::get_links(
+        &thread_address,
+        LinkMatch::Exactly(READ_RECEIPT_LINK_TYPE),
+        LinkMatch::Any,
+    )?
+    .links()
+    .into_iter()
+    .map(|link| ReadPosition {
+        agent_id: link.address,
+        message_address: Address::from(link.tag),
+    })
+    .collect())
+}
+
+// Messages from other participants after what this agent has read.
+// Posting in a thread counts as having read everything before the post
+pub fn unread_count_for(
+    thread_address: &Address,
+    messages: &[MessageWithAddress],
+) -> ZomeApiResult<usize> {
+    let me = Address::from(AGENT_ADDRESS.to_string());
+    let marked = read_marker(thread_address)?
+        .map(|marker| (marker.message_timestamp, marker.message_address));
+    let posted = messages
+        .iter()
+        .filter(|message| message.creator == me)
+        .max_by(|a, b| compare_messages(a, b))
+        .map(|message| (message.timestamp.clone(), message.address.clone()));
+    let read_up_to = match (marked, posted) {
+        (Some(marked), Some(posted)) => {
+            if compare_at((&marked.0, &marked.1), (&posted.0, &posted.1)) == Ordering::Greater {
+                Some(marked)
+            } else {
+                Some(posted)
+            }
+        }
+        (marked, posted) => marked.or(posted),
+    };
+
+    Ok(messages
+        .iter()
+        .filter(|message| message.creator != me)
+        .filter(|message| {
+            read_up_to.as_ref().map_or(true, |(timestamp, address)| {
+                compare_at((&message.timestamp, &message.address), (timestamp, address))
+                    == Ordering::Greater
+            })
+        })
+        .count())
+}
+
+pub fn get_unread_count(thread_address: Address) -> ZomeApiResult<usize> {
+    let messages = get_thread_messages(thread_address.clone())?;
+    unread_count_for(&thread_address, &messages)
+}
+
+pub fn def() -> ValidatingEntryType {
+    entry!(
+        name: READ_MARKER_ENTRY_TYPE,
+        description: "How far the agent has read in a thread. Kept on the agent's own chain",
+        sharing: Sharing::Private,
+
+        validation_package: || {
+            hdk::ValidationPackageDefinition::Entry
+        },
+
+        validation: |_validation_data: hdk::EntryValidationData<ReadMarker>| {
+            Ok(())
+        }
+    )
+}
+
+pub fn receipt_link_def() -> ValidatingLinkDefinition {
+    to!(
+        "%agent_id",
+        link_type: READ_RECEIPT_LINK_TYPE,
+
+        validation_package: || {
+            hdk::ValidationPackageDefinition::Entry
+        },
+
+        // agents can only share their own read position
+        validation: |validation_data: hdk::LinkValidationData| {
+            let (link, validation_data) = match validation_data {
+                LinkValidationData::LinkAdd { link, validation_data } => (link, validation_data),
+                LinkValidationData::LinkRemove { link, validation_data } => (link, validation_data),
+            };
+            if validation_data.sources().contains(link.link().target()) {
+                Ok(())
+            } else {
+                Err("Read receipts can only be made by the reader".into())
+            }
+        }
+    )
+}
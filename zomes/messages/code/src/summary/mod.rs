@@ -0,0 +1,77 @@
+use hdk::{
+    error::ZomeApiResult,
+    holochain_json_api::{error::JsonError, json::JsonString},
+    holochain_persistence_api::cas::content::Address,
+};
+use hdk_helpers::call_zome;
+use std::cmp::Ordering;
+
+use crate::message::MessageWithAddress;
+use crate::receipt::unread_count_for;
+use crate::thread::{compare_messages, get_thread_messages, get_thread_participants, get_threads};
+
+// same shape as people::PersonWithAddress. Participants that never registered have an empty name and avatar
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Participant {
+    pub address: Address,
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub avatar_url: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, DefaultJson)]
+pub struct ThreadSummary {
+    pub address: Address,
+    pub participants: Vec<Participant>,
+    pub latest_message: Option<MessageWithAddress>,
+    pub unread_count: usize,
+}
+
+fn get_participant(agent_id: Address) -> Participant {
+    call_zome("people", "get", json!({ "agent_id": agent_id })).unwrap_or(Participant {
+        address: agent_id,
+        name: String::new(),
+        avatar_url: String::new(),
+    })
+}
+
+fn summarize(thread_address: Address) -> ZomeApiResult<ThreadSummary> {
+    // oldest first, so the latest is last
+    let mut messages = get_thread_messages(thread_address.clone())?;
+    Ok(ThreadSummary {
+        participants: get_thread_participants(thread_address.clone())?
+            .into_iter()
+            .map(get_participant)
+            .collect(),
+        unread_count: unread_count_for(&thread_address, &messages)?,
+        latest_message: messages.pop(),
+        address: thread_address,
+    })
+}
+
+// most recently active first. Threads with no messages go last
+fn compare_activity(a: &ThreadSummary, b: &ThreadSummary) -> Ordering {
+    match (&a.latest_message, &b.latest_message) {
+        (Some(a), Some(b)) => compare_messages(b, a),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => Ordering::Equal,
+    }
+    .then_with(|| a.address.to_string().cmp(&b.address.to_string()))
+}
+
+/**
+ * @brief      Everything the inbox needs to list this agent's threads, in one call
+ *
+ * @return     A summary of each thread the agent is in, most recently active first.
+ *             Threads that can't be read are left out
+ */
+pub fn get_thread_summaries() -> ZomeApiResult<Vec<ThreadSummary>> {
+    let mut summaries: Vec<ThreadSummary> = get_threads()?
+        .into_iter()
+        .filter_map(|thread_address| summarize(thread_address).ok())
+        .collect();
+    summaries.sort_by(compare_activity);
+    Ok(summaries)
+}
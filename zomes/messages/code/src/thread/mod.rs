@@ -1,22 +1,94 @@
 use hdk::{
     self,
     entry_definition::ValidatingEntryType,
-    error::ZomeApiResult,
-    holochain_core_types::{dna::entry_types::Sharing, entry::Entry, link::LinkMatch},
+    error::{ZomeApiError, ZomeApiResult},
+    holochain_core_types::{
+        dna::entry_types::Sharing, entry::Entry, link::LinkMatch, time::Iso8601,
+        validation::EntryValidationData,
+    },
     holochain_json_api::{error::JsonError, json::JsonString},
-    holochain_persistence_api::cas::content::Address,
-    utils, AGENT_ADDRESS,
+    holochain_persistence_api::cas::content::{Address, AddressableContent},
+    utils, LinkValidationData, AGENT_ADDRESS,
 };
 
-use super::message::{get, MessageWithAddress, MESSAGE_ENTRY_TYPE};
+use super::delivery;
+use super::message::{self, get, MessageWithAddress, MESSAGE_ENTRY_TYPE};
+use super::receipt;
+use std::{cmp::Ordering, convert::TryFrom};
 
 pub const THREAD_ENTRY_TYPE: &str = "thread";
 pub const MESSAGE_LINK_TYPE: &str = "message_link_thread";
 pub const AGENT_MESSAGE_THREAD_LINK_TYPE: &str = "agent_message_thread";
 
+// longest allowed thread name, in characters
+const MAX_NAME_LENGTH: usize = 100;
+
 #[derive(Serialize, Deserialize, Debug, Clone, DefaultJson)]
 pub struct Thread {
     pub participants: Vec<String>,
+    // agents who have left. Their earlier messages stay valid
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub left: Vec<String>,
+    // details for group threads. Threads found with find_or_create_thread don't have them,
+    // so that the same people always give the same thread
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub image_url: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub creator: Option<Address>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<String>,
+    // address of the first version, which is what messages and links point at. Set on every later
+    // version so validation can tie a version back to its thread without looking up the latest one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub original: Option<Address>,
+    // set by find_or_create_thread on a thread started again after this agent left the one for the
+    // same people, which keeps it apart from that one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub restarts: Option<Address>,
+}
+
+impl Thread {
+    fn with_participants(participants: Vec<String>) -> Thread {
+        Thread {
+            participants,
+            left: Vec::new(),
+            name: None,
+            image_url: None,
+            creator: None,
+            created_at: None,
+            original: None,
+            restarts: None,
+        }
+    }
+
+    pub fn with_address(&self, address: Address) -> ThreadWithAddress {
+        ThreadWithAddress {
+            address,
+            participants: self.participants.clone(),
+            name: self.name.clone(),
+            image_url: self.image_url.clone(),
+            creator: self.creator.clone(),
+            created_at: self.created_at.clone(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, DefaultJson)]
+pub struct ThreadWithAddress {
+    pub address: Address,
+    pub participants: Vec<String>,
+    pub name: Option<String>,
+    pub image_url: Option<String>,
+    pub creator: Option<Address>,
+    pub created_at: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, DefaultJson)]
+pub struct GetMessagesResult {
+    messages: Vec<MessageWithAddress>,
+    more: bool,
 }
 
 pub fn get_threads() -> ZomeApiResult<Vec<Address>> {
@@ -30,28 +102,394 @@ pub fn get_threads() -> ZomeApiResult<Vec<Address>> {
     .to_owned())
 }
 
-pub fn create_thread(participant_ids: Vec<String>) -> ZomeApiResult<Address> {
+// This agent's threads along with their names, images and participants
+pub fn get_threads_with_details() -> ZomeApiResult<Vec<ThreadWithAddress>> {
+    Ok(get_threads()?
+        .into_iter()
+        .filter_map(|address| {
+            utils::get_as_type::<Thread>(address.clone())
+                .ok()
+                .map(|thread| thread.with_address(address))
+        })
+        .collect())
+}
+
+/**
+ * @brief      Start a thread with a name and picture. Unlike find_or_create_thread, several of these
+ *             can have the same people. The entry is made of its contents though, so the same people,
+ *             name and created_at give back the same thread
+ *
+ * @param      name        Optional title of the thread
+ *
+ * @param      image_url   Optional picture for the thread
+ *
+ * @param      created_at  When the thread was made. Pass it to keep threads with the same people apart
+ */
+pub fn create_thread(
+    participant_ids: Vec<String>,
+    name: Option<String>,
+    image_url: Option<String>,
+    created_at: Option<String>,
+) -> ZomeApiResult<Address> {
     let mut participant_agent_ids = participant_ids.clone();
     participant_agent_ids.push(AGENT_ADDRESS.to_string()); // add this agent to the list
     let thread_entry = Entry::App(
         THREAD_ENTRY_TYPE.into(),
         Thread {
-            participants: participant_agent_ids.clone(),
+            name,
+            image_url,
+            creator: Some(AGENT_ADDRESS.to_string().into()),
+            created_at,
+            ..Thread::with_participants(participant_agent_ids.clone())
         }
         .into(),
     );
     let entry_addr = hdk::commit_entry(&thread_entry)?;
+    link_participants(&entry_addr, &entry_addr, participant_agent_ids)?;
+    Ok(entry_addr)
+}
+
+// the participants including this agent, sorted and without duplicates, so the same set of
+// people always gives the same thread entry
+fn normalize_participants(participant_ids: Vec<String>) -> Vec<String> {
+    let mut participants = participant_ids;
+    participants.push(AGENT_ADDRESS.to_string());
+    participants.sort();
+    participants.dedup();
+    participants
+}
+
+/**
+ * @brief      Get the thread between exactly this agent and the given participants, creating it if needed
+ *
+ * @param      participant_ids     The other participants. Order and duplicates don't matter
+ *
+ * @return     The address of the thread
+ */
+pub fn find_or_create_thread(participant_ids: Vec<String>) -> ZomeApiResult<Address> {
+    let participants = normalize_participants(participant_ids);
+    let me = AGENT_ADDRESS.to_string();
+    let mut thread_entry = Entry::App(
+        THREAD_ENTRY_TYPE.into(),
+        Thread::with_participants(participants.clone()).into(),
+    );
+    // The thread for these people may exist but have had its membership changed since. Bring back
+    // anyone else who left. If others were added it isn't just these people any more, and if this
+    // agent left they can't add themselves back, so in those cases another thread is tried
+    while let Ok(thread) = utils::get_as_type::<Thread>(thread_entry.address()) {
+        let joined_by_others = thread
+            .participants
+            .iter()
+            .any(|participant| !participants.contains(participant));
+        let left = !thread.participants.contains(&me);
+        if !joined_by_others && !left {
+            let missing: Vec<String> = participants
+                .iter()
+                .filter(|participant| !thread.participants.contains(participant))
+                .cloned()
+                .collect();
+            if !missing.is_empty() {
+                add_participants(thread_entry.address(), missing)?;
+            }
+            return Ok(thread_entry.address());
+        }
+        // naming this agent as the creator gives a new thread apart from the one others were added to.
+        // Past that, naming the thread that can't be used gives one apart from it
+        let creator = Some(Address::from(me.clone()));
+        let restarts = if left || thread.creator == creator {
+            Some(thread_entry.address())
+        } else {
+            None
+        };
+        thread_entry = Entry::App(
+            THREAD_ENTRY_TYPE.into(),
+            Thread {
+                creator,
+                restarts,
+                ..Thread::with_participants(participants.clone())
+            }
+            .into(),
+        );
+    }
+
+    // threads made with create_thread aren't normalized so compare the participant sets
+    for thread_address in get_threads()? {
+        if let Ok(thread) = utils::get_as_type::<Thread>(thread_address.clone()) {
+            let mut existing = thread.participants;
+            existing.sort();
+            existing.dedup();
+            if existing == participants {
+                return Ok(thread_address);
+            }
+        }
+    }
+
+    let entry_addr = hdk::commit_entry(&thread_entry)?;
+    link_participants(&entry_addr, &entry_addr, participants)?;
+    Ok(entry_addr)
+}
 
-    for participant_id in participant_agent_ids {
+// Link agents to a thread. The links are tagged with the version of the thread that has them as
+// participants, which is what validation checks them against
+fn link_participants(
+    thread_address: &Address,
+    version: &Address,
+    participant_ids: Vec<String>,
+) -> ZomeApiResult<()> {
+    for participant_id in participant_ids {
         hdk::link_entries(
             &participant_id.into(),
-            &entry_addr,
+            thread_address,
             AGENT_MESSAGE_THREAD_LINK_TYPE,
-            "",
+            &version.to_string(),
         )?;
     }
+    Ok(())
+}
 
-    Ok(entry_addr)
+// The address of the latest version of a thread
+pub fn latest_thread_version(thread_address: &Address) -> ZomeApiResult<Address> {
+    match hdk::get_entry(thread_address)? {
+        Some(entry) => Ok(entry.address()),
+        None => Err(ZomeApiError::Internal("Thread not found".into())),
+    }
+}
+
+// Returns the address of the new version
+fn update_thread(thread_address: &Address, thread: Thread) -> ZomeApiResult<Address> {
+    let latest = match hdk::get_entry(thread_address)? {
+        Some(entry) => entry,
+        None => return Err(ZomeApiError::Internal("Thread not found".into())),
+    };
+    let thread = Thread {
+        original: thread
+            .original
+            .clone()
+            .or_else(|| Some(thread_address.clone())),
+        ..thread
+    };
+    hdk::update_entry(
+        Entry::App(THREAD_ENTRY_TYPE.into(), thread.into()),
+        &latest.address(),
+    )
+}
+
+/**
+ * @brief      Add agents to a thread. They can read and post messages from now on, and the thread
+ *             shows up in their threads. Only participants can do this
+ *
+ * @return     The participants after the change
+ */
+pub fn add_participants(
+    thread_address: Address,
+    participant_ids: Vec<String>,
+) -> ZomeApiResult<Vec<Address>> {
+    let thread = utils::get_as_type::<Thread>(thread_address.clone())?;
+    if !thread.participants.contains(&AGENT_ADDRESS.to_string()) {
+        return Err(ZomeApiError::Internal(
+            "Only participants can add people to a thread".into(),
+        ));
+    }
+    let mut added: Vec<String> = participant_ids
+        .into_iter()
+        .filter(|participant_id| !thread.participants.contains(participant_id))
+        .collect();
+    added.sort();
+    added.dedup();
+    if !added.is_empty() {
+        let mut participants = thread.participants.clone();
+        participants.extend(added.iter().cloned());
+        participants.sort();
+        let left = thread
+            .left
+            .iter()
+            .filter(|agent_id| !added.contains(agent_id))
+            .cloned()
+            .collect();
+        let version = update_thread(
+            &thread_address,
+            Thread {
+                participants,
+                left,
+                ..thread
+            },
+        )?;
+        link_participants(&thread_address, &version, added)?;
+    }
+    get_thread_participants(thread_address)
+}
+
+// Stop being a participant. New messages aren't sent to or readable by this agent
+// and the thread no longer shows up in their threads
+pub fn leave_thread(thread_address: Address) -> ZomeApiResult<Vec<Address>> {
+    let thread = utils::get_as_type::<Thread>(thread_address.clone())?;
+    let me = AGENT_ADDRESS.to_string();
+    if !thread.participants.contains(&me) {
+        return Err(ZomeApiError::Internal(
+            "Not a participant of this thread".into(),
+        ));
+    }
+    let mut left = thread.left;
+    left.push(me.clone());
+    update_thread(
+        &thread_address,
+        Thread {
+            participants: thread
+                .participants
+                .into_iter()
+                .filter(|participant| *participant != me)
+                .collect(),
+            left,
+            ..thread
+        },
+    )?;
+    // the link may have been made against any version of the thread
+    for link in hdk::get_links(
+        &AGENT_ADDRESS,
+        LinkMatch::Exactly(AGENT_MESSAGE_THREAD_LINK_TYPE),
+        LinkMatch::Any,
+    )?
+    .links()
+    {
+        if link.address == thread_address {
+            hdk::remove_link(
+                &AGENT_ADDRESS,
+                &thread_address,
+                AGENT_MESSAGE_THREAD_LINK_TYPE,
+                &link.tag,
+            )?;
+        }
+    }
+    get_thread_participants(thread_address)
+}
+
+// Change the name and picture of a thread. Any participant can do this.
+// Details left out are kept as they are, and an empty string clears one
+pub fn update_thread_details(
+    thread_address: Address,
+    name: Option<String>,
+    image_url: Option<String>,
+) -> ZomeApiResult<ThreadWithAddress> {
+    let thread = utils::get_as_type::<Thread>(thread_address.clone())?;
+    if !thread.participants.contains(&AGENT_ADDRESS.to_string()) {
+        return Err(ZomeApiError::Internal(
+            "Only participants can change a thread".into(),
+        ));
+    }
+    let keep_or_clear = |new: Option<String>, old: Option<String>| match new {
+        Some(value) if value.is_empty() => None,
+        Some(value) => Some(value),
+        None => old,
+    };
+    let updated = Thread {
+        name: keep_or_clear(name, thread.name.clone()),
+        image_url: keep_or_clear(image_url, thread.image_url.clone()),
+        ..thread
+    };
+    update_thread(&thread_address, updated.clone())?;
+    Ok(updated.with_address(thread_address))
+}
+
+fn validate_name(thread: &Thread) -> Result<(), String> {
+    match &thread.name {
+        Some(name) if name.chars().count() > MAX_NAME_LENGTH => Err(format!(
+            "Thread names can be at most {} characters",
+            MAX_NAME_LENGTH
+        )),
+        _ => Ok(()),
+    }
+}
+
+// Participants may add others, remove only themselves, and rename the thread
+fn validate_membership_change(
+    old_thread: &Thread,
+    new_thread: &Thread,
+    sources: &[Address],
+) -> Result<(), String> {
+    let sources: Vec<String> = sources.iter().map(|source| source.to_string()).collect();
+    if !sources
+        .iter()
+        .all(|source| old_thread.participants.contains(source))
+    {
+        return Err("Only participants can change who is in a thread".into());
+    }
+    let removed: Vec<&String> = old_thread
+        .participants
+        .iter()
+        .filter(|participant| !new_thread.participants.contains(participant))
+        .collect();
+    if removed
+        .iter()
+        .any(|participant| !sources.contains(participant))
+    {
+        return Err("Participants can only remove themselves from a thread".into());
+    }
+    if removed
+        .iter()
+        .any(|participant| !new_thread.left.contains(participant))
+    {
+        return Err("Participants who leave must be recorded as having left".into());
+    }
+    if new_thread.creator != old_thread.creator
+        || new_thread.created_at != old_thread.created_at
+        || new_thread.restarts != old_thread.restarts
+    {
+        return Err("The creator and creation time of a thread cannot be changed".into());
+    }
+    // threads from before versions were tied to their first one get it on their next change
+    match (&old_thread.original, &new_thread.original) {
+        (Some(old), Some(new)) if old == new => (),
+        (None, Some(_)) => (),
+        _ => return Err("Every later version of a thread must point at its first version".into()),
+    }
+    validate_name(new_thread)
+}
+
+// An agent may be linked to a thread by a participant of the version named in the link tag, if that
+// version has the agent as a participant. Versions can't change once committed, so every validator
+// gets the same answer
+fn validate_participant_link(
+    thread_address: &Address,
+    agent_id: &Address,
+    version: &str,
+    sources: &[Address],
+) -> Result<(), String> {
+    let thread = thread_version(thread_address, &Address::from(version)).map_err(|_| {
+        String::from("Participant links must be tagged with a version of the thread")
+    })?;
+    if !thread.participants.contains(&agent_id.to_string()) {
+        return Err("Only participants can be linked to a thread".into());
+    }
+    if !sources
+        .iter()
+        .all(|source| thread.participants.contains(&source.to_string()))
+    {
+        return Err("Only participants can link others to a thread".into());
+    }
+    Ok(())
+}
+
+// A version of a thread exactly as it was committed. Versions can't change, so unlike the latest
+// version this gives every validator the same answer
+pub fn thread_version(thread_address: &Address, version: &Address) -> Result<Thread, String> {
+    let thread = match hdk::get_entry_initial(version) {
+        Ok(Some(Entry::App(entry_type, content)))
+            if String::from(entry_type) == THREAD_ENTRY_TYPE =>
+        {
+            Thread::try_from(content).map_err(|_| String::from("Could not read the thread"))?
+        }
+        _ => return Err("Could not load the thread version".into()),
+    };
+    if version != thread_address && thread.original.as_ref() != Some(thread_address) {
+        return Err("Not a version of this thread".into());
+    }
+    Ok(thread)
+}
+
+pub fn is_participant(thread_address: &Address, agent_id: &Address) -> ZomeApiResult<bool> {
+    Ok(utils::get_as_type::<Thread>(thread_address.clone())?
+        .participants
+        .contains(&agent_id.to_string()))
 }
 
 pub fn get_thread_participants(thread_address: Address) -> ZomeApiResult<Vec<Address>> {
@@ -62,16 +500,143 @@ pub fn get_thread_participants(thread_address: Address) -> ZomeApiResult<Vec<Add
         .collect())
 }
 
-pub fn get_thread_messages(thread_address: Address) -> ZomeApiResult<Vec<MessageWithAddress>> {
-    Ok(hdk::get_links(
-        &thread_address,
-        LinkMatch::Exactly(MESSAGE_LINK_TYPE.into()),
+// Where a message sits in its thread. Read from the tag of the link from the thread where there is one
+#[derive(Clone)]
+struct Position {
+    address: Address,
+    timestamp: String,
+}
+
+// Timestamps are compared parsed, since the same time can be written with different offsets.
+// Ones that can't be parsed sort first. Ties are broken on address
+pub fn compare_at(a: (&str, &Address), b: (&str, &Address)) -> Ordering {
+    let parse = |timestamp: &str| Iso8601::try_from(timestamp).ok();
+    parse(a.0)
+        .cmp(&parse(b.0))
+        .then_with(|| a.1.to_string().cmp(&b.1.to_string()))
+}
+
+fn compare_positions(a: &Position, b: &Position) -> Ordering {
+    compare_at((&a.timestamp, &a.address), (&b.timestamp, &b.address))
+}
+
+// Messages linked from the thread plus any delivered directly that haven't reached the DHT yet, oldest first.
+// Only messages linked before the links were tagged need to be loaded
+fn message_positions(thread_address: &Address) -> ZomeApiResult<Vec<Position>> {
+    let mut positions: Vec<Position> = hdk::get_links(
+        thread_address,
+        LinkMatch::Exactly(MESSAGE_LINK_TYPE),
         LinkMatch::Any,
     )?
-    .addresses()
-    .iter()
-    .map(|address| get(address.to_string().into()).unwrap())
-    .collect())
+    .links()
+    .into_iter()
+    .filter_map(|link| {
+        // untagged links are from before message links were validated, so check the thread too
+        let timestamp = if link.tag.is_empty() {
+            let (timestamp, thread) = message::timestamp_and_thread(&link.address).ok()?;
+            if thread != *thread_address {
+                return None;
+            }
+            timestamp
+        } else {
+            link.tag
+        };
+        Some(Position {
+            address: link.address,
+            timestamp,
+        })
+    })
+    .collect();
+    for delivered in delivery::delivered_to_thread(thread_address)? {
+        if !positions
+            .iter()
+            .any(|position| position.address == delivered.address)
+        {
+            positions.push(Position {
+                address: delivered.address,
+                timestamp: delivered.message.timestamp,
+            });
+        }
+    }
+    positions.sort_by(compare_positions);
+    Ok(positions)
+}
+
+// Messages found on the DHT plus any delivered directly that haven't reached the DHT yet
+pub fn get_thread_messages(thread_address: Address) -> ZomeApiResult<Vec<MessageWithAddress>> {
+    Ok(message_positions(&thread_address)?
+        .into_iter()
+        .filter_map(|position| get(position.address).ok())
+        .filter(|message| message.thread_address == thread_address)
+        .collect())
+}
+
+// oldest first. Ties are broken on address so every participant sees the same order
+pub fn compare_messages(a: &MessageWithAddress, b: &MessageWithAddress) -> Ordering {
+    compare_at((&a.timestamp, &a.address), (&b.timestamp, &b.address))
+}
+
+/**
+ * @brief      Get a page of the messages in a thread, oldest first. The page is worked out from the link
+ *             tags so only the messages returned are loaded
+ *
+ * @param      before      Address of a message. Only messages before it are returned, for scrolling back
+ *
+ * @param      after       Address of a message. Only messages after it are returned, for polling for new ones
+ *
+ * @param      limit       Maximum number of messages to return. These are the oldest ones after `after` if that
+ *                         is given, otherwise the newest ones (before `before`)
+ *
+ * @return     The messages and a bool which is true if the limit left some out
+ */
+pub fn get_thread_messages_page(
+    thread_address: Address,
+    before: Option<Address>,
+    after: Option<Address>,
+    limit: Option<usize>,
+) -> ZomeApiResult<GetMessagesResult> {
+    let positions = message_positions(&thread_address)?;
+    // unsent messages are unlinked but still mark their place
+    let cursor = |address: Address| -> ZomeApiResult<Position> {
+        match positions
+            .iter()
+            .find(|position| position.address == address)
+        {
+            Some(position) => Ok(position.clone()),
+            None => Ok(Position {
+                timestamp: message::timestamp(&address)?,
+                address,
+            }),
+        }
+    };
+    let before = before.map(cursor).transpose()?;
+    let after = after.map(cursor).transpose()?;
+
+    let mut page: Vec<&Position> = positions
+        .iter()
+        .filter(|position| {
+            after.as_ref().map_or(true, |after| {
+                compare_positions(position, after) == Ordering::Greater
+            }) && before.as_ref().map_or(true, |before| {
+                compare_positions(position, before) == Ordering::Less
+            })
+        })
+        .collect();
+
+    let limit = limit.unwrap_or(page.len());
+    let more = page.len() > limit;
+    if after.is_some() {
+        page.truncate(limit);
+    } else {
+        page.drain(..page.len().saturating_sub(limit));
+    }
+
+    let messages = page
+        .into_iter()
+        .filter_map(|position| get(position.address.clone()).ok())
+        .filter(|message| message.thread_address == thread_address)
+        .collect();
+    Ok(GetMessagesResult { messages, more })
 }
 
 pub fn def() -> ValidatingEntryType {
@@ -84,8 +649,28 @@ pub fn def() -> ValidatingEntryType {
             hdk::ValidationPackageDefinition::Entry
         },
 
-        validation: |_validation_data: hdk::EntryValidationData<Thread>| {
-            Ok(())
+        validation: |validation_data: hdk::EntryValidationData<Thread>| {
+            match validation_data {
+                EntryValidationData::Create{ entry, validation_data } => {
+                    if !validation_data
+                        .sources()
+                        .iter()
+                        .all(|source| entry.participants.contains(&source.to_string()))
+                    {
+                        return Err("The creator of a thread must be one of its participants".into());
+                    }
+                    if let Some(creator) = &entry.creator {
+                        if !validation_data.sources().contains(creator) {
+                            return Err("The creator of a thread must be the agent committing it".into());
+                        }
+                    }
+                    validate_name(&entry)
+                },
+                EntryValidationData::Modify{ new_entry, old_entry, validation_data, .. } => {
+                    validate_membership_change(&old_entry, &new_entry, &validation_data.sources())
+                },
+                EntryValidationData::Delete{ .. } => Err("Threads cannot be deleted".into()),
+            }
         },
 
         links: [
@@ -97,8 +682,26 @@ pub fn def() -> ValidatingEntryType {
                     hdk::ValidationPackageDefinition::Entry
                 },
 
-                validation: |_validation_data: hdk::LinkValidationData| {
-                    Ok(())
+                // only participants can have the thread linked from their agent, and only they can unlink it
+                validation: |validation_data: hdk::LinkValidationData| {
+                    match validation_data {
+                        LinkValidationData::LinkAdd { link, validation_data } => {
+                            let link = link.link();
+                            validate_participant_link(
+                                link.target(),
+                                link.base(),
+                                link.tag(),
+                                &validation_data.sources(),
+                            )
+                        }
+                        LinkValidationData::LinkRemove { link, validation_data } => {
+                            if validation_data.sources().contains(link.link().base()) {
+                                Ok(())
+                            } else {
+                                Err("Only the agent can remove a thread from their threads".into())
+                            }
+                        }
+                    }
                 }
             ),
             to!(
@@ -109,10 +712,11 @@ pub fn def() -> ValidatingEntryType {
                     hdk::ValidationPackageDefinition::Entry
                 },
 
-                validation: |_validation_data: hdk::LinkValidationData| {
-                    Ok(())
+                validation: |validation_data: hdk::LinkValidationData| {
+                    message::validate_message_link(validation_data)
                 }
-            )
+            ),
+            receipt::receipt_link_def()
         ]
     )
 }
@@ -4,6 +4,10 @@ use hdk::{
     holochain_core_types::dna::entry_types::Sharing,
     holochain_json_api::{error::JsonError, json::JsonString},
 };
+
+use crate::directory;
+use crate::handle;
+
 pub const ANCHOR_ENTRY_TYPE: &str = "anchor";
 pub const ANCHOR_PERSON_LINK_TYPE: &str = "registered";
 
@@ -38,7 +42,10 @@ pub fn def() -> ValidatingEntryType {
                 validation: |_validation_data: hdk::LinkValidationData| {
                     Ok(())
                 }
-            )
+            ),
+            handle::handle_link_def(),
+            directory::listing_link_def(),
+            directory::bucket_link_def()
         ]
     )
 }
@@ -0,0 +1,391 @@
+use hdk::{
+    self,
+    entry_definition::ValidatingLinkDefinition,
+    error::{ZomeApiError, ZomeApiResult},
+    holochain_core_types::{entry::Entry, link::LinkMatch},
+    holochain_json_api::{error::JsonError, json::JsonString},
+    holochain_persistence_api::cas::content::{Address, AddressableContent},
+    LinkValidationData, AGENT_ADDRESS,
+};
+use hdk_helpers::commit_if_not_in_chain;
+
+use crate::anchor::{Anchor, ANCHOR_ENTRY_TYPE, ANCHOR_PERSON_LINK_TYPE};
+use crate::mentions::normalize;
+use crate::people::{self, Person, PersonWithAddress};
+
+// "people:<prefix>" bucket anchor -> an agent whose name or handle starts with the prefix.
+// Tagged with the full search key so a bucket can be filtered without loading every profile.
+// Handle keys are tagged with a leading @ to tell them apart from names
+pub const LISTING_LINK_TYPE: &str = "listed";
+// bucket index anchor -> each bucket anchor in use, tagged with its prefix
+pub const BUCKET_LINK_TYPE: &str = "has_bucket";
+
+const BUCKET_ANCHOR_PREFIX: &str = "people:";
+const BUCKET_INDEX_ANCHOR: &str = "people-buckets";
+const HANDLE_TAG_PREFIX: &str = "@";
+// searches are spread over one anchor per this many leading characters rather than all
+// landing on the single "people" anchor
+const BUCKET_PREFIX_LENGTH: usize = 2;
+pub const DEFAULT_PAGE_SIZE: usize = 20;
+
+#[derive(Serialize, Deserialize, Debug, Clone, DefaultJson)]
+pub struct DirectoryResult {
+    people: Vec<PersonWithAddress>,
+    more: bool,
+}
+
+fn anchor(name: String) -> Entry {
+    Entry::App(ANCHOR_ENTRY_TYPE.into(), Anchor { name }.into())
+}
+
+fn bucket_prefix(key: &str) -> String {
+    key.chars().take(BUCKET_PREFIX_LENGTH).collect()
+}
+
+fn bucket_anchor(key: &str) -> Entry {
+    anchor(format!("{}{}", BUCKET_ANCHOR_PREFIX, bucket_prefix(key)))
+}
+
+fn bucket_index_anchor() -> Entry {
+    anchor(BUCKET_INDEX_ANCHOR.into())
+}
+
+// the search key for a name: lowercase without spaces or a leading @
+fn name_key(name: &str) -> String {
+    normalize(name.trim_start_matches(HANDLE_TAG_PREFIX))
+}
+
+// the key a listing tag is searched by
+fn tag_key(tag: &str) -> &str {
+    tag.trim_start_matches(HANDLE_TAG_PREFIX)
+}
+
+// the tags a profile is listed under: its name key, and its handle after an @
+fn listing_tags(person: &Person) -> Vec<String> {
+    let mut tags = Vec::new();
+    let name = name_key(&person.name);
+    if !name.is_empty() {
+        tags.push(name);
+    }
+    if let Some(handle) = &person.handle {
+        tags.push(format!("{}{}", HANDLE_TAG_PREFIX, handle));
+    }
+    tags
+}
+
+fn has_link(base: &Address, link_type: &str, tag: &str) -> ZomeApiResult<bool> {
+    Ok(
+        !hdk::get_links(base, LinkMatch::Exactly(link_type), LinkMatch::Exactly(tag))?
+            .addresses()
+            .is_empty(),
+    )
+}
+
+// List this agent under the buckets for their name and handle. Links already made are left alone,
+// so this is safe to call again
+pub fn index(person: &Person) -> ZomeApiResult<()> {
+    let me = Address::from(AGENT_ADDRESS.to_string());
+    let index_address = commit_if_not_in_chain(&bucket_index_anchor())?;
+    for tag in listing_tags(person) {
+        let prefix = bucket_prefix(tag_key(&tag));
+        let anchor_address = commit_if_not_in_chain(&bucket_anchor(&prefix))?;
+        if !has_link(&index_address, BUCKET_LINK_TYPE, &prefix)? {
+            hdk::link_entries(&index_address, &anchor_address, BUCKET_LINK_TYPE, &prefix)?;
+        }
+        let listed = hdk::get_links(
+            &anchor_address,
+            LinkMatch::Exactly(LISTING_LINK_TYPE),
+            LinkMatch::Exactly(tag.as_str()),
+        )?
+        .addresses()
+        .contains(&me);
+        if !listed {
+            hdk::link_entries(&anchor_address, &me, LISTING_LINK_TYPE, &tag)?;
+        }
+    }
+    Ok(())
+}
+
+// Take this agent out of the buckets for an old version of their profile.
+// Profiles from before the directory was bucketed were never listed, so only existing links are removed
+pub fn unindex(person: &Person) -> ZomeApiResult<()> {
+    let me = Address::from(AGENT_ADDRESS.to_string());
+    for tag in listing_tags(person) {
+        let anchor_address = bucket_anchor(tag_key(&tag)).address();
+        let listed = hdk::get_links(
+            &anchor_address,
+            LinkMatch::Exactly(LISTING_LINK_TYPE),
+            LinkMatch::Exactly(tag.as_str()),
+        )?
+        .addresses()
+        .contains(&me);
+        if listed {
+            hdk::remove_link(&anchor_address, &me, LISTING_LINK_TYPE, &tag)?;
+        }
+    }
+    Ok(())
+}
+
+// Profiles registered before the directory existed aren't listed in it. This lists one if its name
+// isn't, and is called whenever an agent reads their own profile
+pub fn ensure_indexed(person: &Person) -> ZomeApiResult<()> {
+    let key = name_key(&person.name);
+    if key.is_empty() {
+        return Ok(());
+    }
+    let me = Address::from(AGENT_ADDRESS.to_string());
+    let indexed = agents_with_key(&key)?.contains(&me)
+        && has_link(
+            &bucket_index_anchor().address(),
+            BUCKET_LINK_TYPE,
+            &bucket_prefix(&key),
+        )?;
+    if indexed {
+        Ok(())
+    } else {
+        index(person)
+    }
+}
+
+// agents listed under a search prefix, in no particular order. A search shorter than the bucket
+// prefix scans every bucket whose prefix starts with it, found through the bucket index
+fn search_matches(search: &str) -> ZomeApiResult<Vec<Address>> {
+    let mut prefixes = vec![bucket_prefix(search)];
+    if search.chars().count() < BUCKET_PREFIX_LENGTH {
+        prefixes.extend(
+            hdk::get_links(
+                &bucket_index_anchor().address(),
+                LinkMatch::Exactly(BUCKET_LINK_TYPE),
+                LinkMatch::Any,
+            )?
+            .links()
+            .into_iter()
+            .map(|link| link.tag)
+            .filter(|prefix| prefix.starts_with(search)),
+        );
+        prefixes.sort();
+        prefixes.dedup();
+    }
+    let mut agents = Vec::new();
+    for prefix in prefixes {
+        for link in hdk::get_links(
+            &bucket_anchor(&prefix).address(),
+            LinkMatch::Exactly(LISTING_LINK_TYPE),
+            LinkMatch::Any,
+        )?
+        .links()
+        {
+            if tag_key(&link.tag).starts_with(search) && !agents.contains(&link.address) {
+                agents.push(link.address);
+            }
+        }
+    }
+    Ok(agents)
+}
+
+// agents whose name is exactly this search key. Reads only the one bucket's links
+pub fn agents_with_key(key: &str) -> ZomeApiResult<Vec<Address>> {
+    Ok(hdk::get_links(
+        &bucket_anchor(key).address(),
+        LinkMatch::Exactly(LISTING_LINK_TYPE),
+        LinkMatch::Exactly(key),
+    )?
+    .addresses())
+}
+
+// every agent linked from the people anchor
+pub fn registered_agents() -> ZomeApiResult<Vec<Address>> {
+    Ok(hdk::get_links(
+        &anchor("people".into()).address(),
+        LinkMatch::Exactly(ANCHOR_PERSON_LINK_TYPE.into()),
+        LinkMatch::Any,
+    )?
+    .addresses())
+}
+
+// Everyone listed by name, one page at a time. Walks the buckets in order of their prefixes, which
+// is also the order of the names in them, and only loads the profiles that make it onto the page.
+// Also returns whether there are more after the page
+fn browse(after: Option<Address>, limit: usize) -> ZomeApiResult<(Vec<PersonWithAddress>, bool)> {
+    let after = match after {
+        Some(address) => {
+            let person = people::get(address.clone())
+                .map_err(|_| ZomeApiError::Internal("Cursor does not match any person".into()))?;
+            Some((name_key(&person.name), address.to_string()))
+        }
+        None => None,
+    };
+    let mut prefixes: Vec<String> = hdk::get_links(
+        &bucket_index_anchor().address(),
+        LinkMatch::Exactly(BUCKET_LINK_TYPE),
+        LinkMatch::Any,
+    )?
+    .links()
+    .into_iter()
+    .map(|link| link.tag)
+    .collect();
+    prefixes.sort();
+    prefixes.dedup();
+
+    let mut people = Vec::new();
+    for prefix in prefixes {
+        // earlier buckets only hold names before the cursor
+        if let Some((key, _)) = &after {
+            if prefix < bucket_prefix(key) {
+                continue;
+            }
+        }
+        let mut listed: Vec<(String, String)> = hdk::get_links(
+            &bucket_anchor(&prefix).address(),
+            LinkMatch::Exactly(LISTING_LINK_TYPE),
+            LinkMatch::Any,
+        )?
+        .links()
+        .into_iter()
+        .filter(|link| !link.tag.starts_with(HANDLE_TAG_PREFIX))
+        .map(|link| (link.tag, link.address.to_string()))
+        .filter(|listing| after.as_ref().map_or(true, |after| listing > after))
+        .collect();
+        listed.sort();
+        listed.dedup();
+        for (_, agent_id) in listed {
+            if people.len() == limit {
+                return Ok((people, true));
+            }
+            if let Ok(person) = people::get(Address::from(agent_id)) {
+                people.push(person);
+            }
+        }
+    }
+    Ok((people, false))
+}
+
+/**
+ * @brief      List people one page at a time, sorted by name
+ *
+ * @param      search      Only include people whose name (ignoring case and spaces) or handle starts with this
+ *
+ * @param      after       Address of the last person of the previous page. Omit to get the first page
+ *
+ * @param      limit       Page size
+ *
+ * @return     The page of people and whether there are more after it. Agents without a profile are left out
+ */
+pub fn directory(
+    search: Option<String>,
+    after: Option<Address>,
+    limit: Option<usize>,
+) -> ZomeApiResult<DirectoryResult> {
+    let limit = limit.unwrap_or(DEFAULT_PAGE_SIZE);
+    let search = search
+        .map(|search| normalize(search.trim_start_matches(HANDLE_TAG_PREFIX)))
+        .filter(|search| !search.is_empty());
+    let (people, more) = match search {
+        Some(search) => {
+            // a listing under a handle stays after the handle is released, so people are matched
+            // again against the profile as it is shown
+            let found = search_matches(&search)?
+                .into_iter()
+                .filter_map(|agent_id| people::get(agent_id).ok())
+                .filter(|person| {
+                    name_key(&person.name).starts_with(&search)
+                        || person
+                            .handle
+                            .as_ref()
+                            .map_or(false, |handle| handle.starts_with(&search))
+                })
+                .collect();
+            page(found, after, limit)?
+        }
+        None => browse(after, limit)?,
+    };
+
+    Ok(DirectoryResult { people, more })
+}
+
+// Sort search results by name and cut out the page following the `after` cursor.
+// Also returns whether there are more after the page
+fn page(
+    mut items: Vec<PersonWithAddress>,
+    after: Option<Address>,
+    limit: usize,
+) -> ZomeApiResult<(Vec<PersonWithAddress>, bool)> {
+    items.sort_by(|a, b| {
+        // by the same key as the unfiltered directory, so both list people in the same order
+        name_key(&a.name)
+            .cmp(&name_key(&b.name))
+            .then_with(|| a.address.to_string().cmp(&b.address.to_string()))
+    });
+
+    let start = match after {
+        Some(after) => {
+            items
+                .iter()
+                .position(|person| person.address == after)
+                .ok_or(ZomeApiError::Internal(
+                    "Cursor does not match any person".into(),
+                ))?
+                + 1
+        }
+        None => 0,
+    };
+    let more = items.len() > start + limit;
+    Ok((items.into_iter().skip(start).take(limit).collect(), more))
+}
+
+pub fn listing_link_def() -> ValidatingLinkDefinition {
+    to!(
+        "%agent_id",
+        link_type: LISTING_LINK_TYPE,
+
+        validation_package: || {
+            hdk::ValidationPackageDefinition::Entry
+        },
+
+        // agents list and unlist themselves
+        validation: |validation_data: hdk::LinkValidationData| {
+            let (link, validation_data) = match validation_data {
+                LinkValidationData::LinkAdd { link, validation_data } => (link, validation_data),
+                LinkValidationData::LinkRemove { link, validation_data } => (link, validation_data),
+            };
+            if validation_data.sources().contains(link.link().target()) {
+                Ok(())
+            } else {
+                Err("Agents can only change their own directory listing".into())
+            }
+        }
+    )
+}
+
+pub fn bucket_link_def() -> ValidatingLinkDefinition {
+    to!(
+        ANCHOR_ENTRY_TYPE,
+        link_type: BUCKET_LINK_TYPE,
+
+        validation_package: || {
+            hdk::ValidationPackageDefinition::Entry
+        },
+
+        // anyone can list a bucket, as long as the link goes from the index to the bucket its tag names.
+        // Buckets stay listed once they are in use
+        validation: |validation_data: hdk::LinkValidationData| {
+            match validation_data {
+                LinkValidationData::LinkAdd { link, .. } => {
+                    let link = link.link();
+                    let prefix = link.tag();
+                    if prefix.is_empty() || bucket_prefix(prefix) != *prefix {
+                        return Err("Not a bucket prefix".into());
+                    }
+                    if *link.base() == bucket_index_anchor().address()
+                        && *link.target() == bucket_anchor(prefix).address()
+                    {
+                        Ok(())
+                    } else {
+                        Err("Buckets must be linked from the bucket index to their own anchor".into())
+                    }
+                },
+                LinkValidationData::LinkRemove { .. } => Err("Buckets cannot be unlisted".into()),
+            }
+        }
+    )
+}
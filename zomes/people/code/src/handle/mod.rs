@@ -0,0 +1,238 @@
+use hdk::{
+    self,
+    entry_definition::{ValidatingEntryType, ValidatingLinkDefinition},
+    error::{ZomeApiError, ZomeApiResult},
+    holochain_core_types::{
+        dna::entry_types::Sharing, entry::Entry, link::LinkMatch, validation::EntryValidationData,
+    },
+    holochain_json_api::{error::JsonError, json::JsonString},
+    holochain_persistence_api::cas::content::{Address, AddressableContent},
+    holochain_wasm_utils::api_serialization::get_links::GetLinksOptions,
+    utils, LinkValidationData, AGENT_ADDRESS,
+};
+use hdk_helpers::commit_if_not_in_chain;
+
+use crate::anchor::{Anchor, ANCHOR_ENTRY_TYPE};
+use crate::people::{self, PersonWithAddress};
+
+// "handle:<handle>" anchor -> the agent who claimed it
+pub const HANDLE_LINK_TYPE: &str = "handle_of";
+pub const HANDLE_CLAIM_ENTRY_TYPE: &str = "handle_claim";
+
+const HANDLE_ANCHOR_PREFIX: &str = "handle:";
+const MIN_HANDLE_LENGTH: usize = 3;
+const MAX_HANDLE_LENGTH: usize = 30;
+
+// handles are case insensitive and may be written with their @
+pub fn normalize_handle(handle: &str) -> String {
+    handle.trim().trim_start_matches('@').to_lowercase()
+}
+
+// lowercase letters, digits and underscores, starting with a letter
+pub fn validate_handle(handle: &str) -> Result<(), String> {
+    if handle.len() < MIN_HANDLE_LENGTH || handle.len() > MAX_HANDLE_LENGTH {
+        return Err(format!(
+            "Handles must be between {} and {} characters",
+            MIN_HANDLE_LENGTH, MAX_HANDLE_LENGTH
+        ));
+    }
+    if !handle.starts_with(|c: char| c.is_ascii_lowercase())
+        || !handle
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+    {
+        return Err(
+            "Handles can only use lowercase letters, digits and _, starting with a letter".into(),
+        );
+    }
+    Ok(())
+}
+
+fn handle_anchor(handle: &str) -> Entry {
+    Entry::App(
+        ANCHOR_ENTRY_TYPE.into(),
+        Anchor {
+            name: format!("{}{}", HANDLE_ANCHOR_PREFIX, handle),
+        }
+        .into(),
+    )
+}
+
+// Committed by an agent along with their handle_of link. Its address can be worked out from the
+// handle and agent, so profile validation can check a claim with a single get_entry
+#[derive(Serialize, Deserialize, Debug, Clone, DefaultJson)]
+pub struct HandleClaim {
+    pub handle: String,
+    pub agent_id: Address,
+}
+
+fn claim_entry(handle: &str, agent_id: &Address) -> Entry {
+    Entry::App(
+        HANDLE_CLAIM_ENTRY_TYPE.into(),
+        HandleClaim {
+            handle: handle.to_string(),
+            agent_id: agent_id.clone(),
+        }
+        .into(),
+    )
+}
+
+// The agent must have claimed the handle. Doesn't tell whether someone else claimed it first or
+// whether it was released since, so profiles only show a handle while owner_of names their agent
+pub fn validate_claimed(handle: &str, agent_id: &Address) -> Result<(), String> {
+    let claimed = hdk::entry_address(&claim_entry(handle, agent_id))
+        .and_then(|address| hdk::get_entry(&address));
+    match claimed {
+        Ok(Some(_)) => Ok(()),
+        _ => Err(format!(
+            "The handle {} has not been claimed by this agent",
+            handle
+        )),
+    }
+}
+
+/**
+ * @brief      The agent who has claimed a (normalized) handle
+ *
+ * @return     If more than one agent claimed it, the one whose claim was made first, then the lowest
+ *             address, so every lookup agrees on the owner. The claim times are the link header
+ *             timestamps, which the claiming agent sets, so an agent can backdate a claim to win the
+ *             tie-break. This only keeps lookups consistent, it doesn't prove who was first
+ */
+pub fn owner_of(handle: &str) -> ZomeApiResult<Option<Address>> {
+    let options = GetLinksOptions {
+        headers: true,
+        ..GetLinksOptions::default()
+    };
+    Ok(hdk::get_links_with_options(
+        &handle_anchor(handle).address(),
+        LinkMatch::Exactly(HANDLE_LINK_TYPE),
+        LinkMatch::Any,
+        options,
+    )?
+    .links()
+    .into_iter()
+    .filter_map(|link| {
+        link.headers
+            .iter()
+            .map(|header| header.timestamp().clone())
+            .min()
+            .map(|claimed_at| (claimed_at, link.address))
+    })
+    .min_by(|(a_time, a), (b_time, b)| {
+        a_time
+            .cmp(b_time)
+            .then_with(|| a.to_string().cmp(&b.to_string()))
+    })
+    .map(|(_, agent_id)| agent_id))
+}
+
+// Make a handle this agent's. Fails if someone else already has it
+pub fn claim(handle: &str) -> ZomeApiResult<()> {
+    validate_handle(handle).map_err(ZomeApiError::Internal)?;
+    let me = Address::from(AGENT_ADDRESS.to_string());
+    match owner_of(handle)? {
+        // handles claimed before claim entries were made don't have one yet
+        Some(owner) if owner == me => commit_if_not_in_chain(&claim_entry(handle, &me)).map(|_| ()),
+        Some(_) => Err(ZomeApiError::Internal(format!(
+            "The handle {} is already taken",
+            handle
+        ))),
+        None => {
+            commit_if_not_in_chain(&claim_entry(handle, &me))?;
+            let anchor_address = commit_if_not_in_chain(&handle_anchor(handle))?;
+            hdk::link_entries(&anchor_address, &me, HANDLE_LINK_TYPE, "")?;
+            Ok(())
+        }
+    }
+}
+
+// Give up a handle so others can claim it
+pub fn release(handle: &str) -> ZomeApiResult<()> {
+    hdk::remove_link(
+        &handle_anchor(handle).address(),
+        &AGENT_ADDRESS,
+        HANDLE_LINK_TYPE,
+        "",
+    )
+}
+
+pub fn is_handle_available(handle: String) -> ZomeApiResult<bool> {
+    let handle = normalize_handle(&handle);
+    if validate_handle(&handle).is_err() {
+        return Ok(false);
+    }
+    Ok(owner_of(&handle)?.is_none())
+}
+
+pub fn get_by_handle(handle: String) -> ZomeApiResult<PersonWithAddress> {
+    match owner_of(&normalize_handle(&handle))? {
+        Some(agent_id) => people::get(agent_id),
+        None => Err(ZomeApiError::Internal("No one has this handle".into())),
+    }
+}
+
+pub fn handle_link_def() -> ValidatingLinkDefinition {
+    to!(
+        "%agent_id",
+        link_type: HANDLE_LINK_TYPE,
+
+        validation_package: || {
+            hdk::ValidationPackageDefinition::Entry
+        },
+
+        // agents can only claim valid handles for themselves. Whether someone else got there first
+        // can't be checked the same way by every validator, so owner_of settles that instead
+        validation: |validation_data: hdk::LinkValidationData| {
+            match validation_data {
+                LinkValidationData::LinkAdd { link, validation_data } => {
+                    let link = link.link();
+                    if !validation_data.sources().contains(link.target()) {
+                        return Err("Agents can only claim handles for themselves".into());
+                    }
+                    let anchor = utils::get_as_type::<Anchor>(link.base().clone())
+                        .map_err(|_| String::from("Could not load the handle anchor"))?;
+                    if !anchor.name.starts_with(HANDLE_ANCHOR_PREFIX) {
+                        return Err("Not a handle anchor".into());
+                    }
+                    let handle = &anchor.name[HANDLE_ANCHOR_PREFIX.len()..];
+                    validate_handle(handle)?;
+                    validate_claimed(handle, link.target())
+                },
+                LinkValidationData::LinkRemove { link, validation_data } => {
+                    if validation_data.sources().contains(link.link().target()) {
+                        Ok(())
+                    } else {
+                        Err("Only the agent with a handle can give it up".into())
+                    }
+                },
+            }
+        }
+    )
+}
+
+pub fn claim_def() -> ValidatingEntryType {
+    entry!(
+        name: HANDLE_CLAIM_ENTRY_TYPE,
+        description: "An agent's claim on a handle",
+        sharing: Sharing::Public,
+
+        validation_package: || {
+            hdk::ValidationPackageDefinition::Entry
+        },
+
+        validation: |validation_data: hdk::EntryValidationData<HandleClaim>| {
+            match validation_data {
+                EntryValidationData::Create{ entry, validation_data } => {
+                    validate_handle(&entry.handle)?;
+                    if validation_data.sources().contains(&entry.agent_id) {
+                        Ok(())
+                    } else {
+                        Err("Agents can only claim handles for themselves".into())
+                    }
+                },
+                _ => Err("Handle claims cannot be changed".into()),
+            }
+        }
+    )
+}
@@ -3,12 +3,17 @@ extern crate hdk;
 extern crate serde;
 #[macro_use]
 extern crate serde_derive;
+#[macro_use]
 extern crate serde_json;
 #[macro_use]
 extern crate holochain_json_derive;
 extern crate derive_more;
 
 mod anchor;
+mod directory;
+mod handle;
+mod members;
+mod mentions;
 mod people;
 
 use hdk::{
@@ -17,11 +22,13 @@ use hdk::{
     holochain_json_api::{error::JsonError, json::JsonString},
     holochain_persistence_api::cas::content::Address,
 };
+use hdk_helpers::communities::Role;
 
 define_zome! {
     entries: [
         anchor::def(),
-        people::def()
+        people::def(),
+        handle::claim_def()
     ]
 
     init: || { Ok(()) }
@@ -56,15 +63,55 @@ define_zome! {
             handler: people::is_registered
         }
         register_user: {
-            inputs: |name: String, avatar_url:String|,
+            inputs: |name: String, avatar_url:String, handle: Option<String>, details: Option<people::ProfileDetails>|,
             outputs: |result: ZomeApiResult<people::PersonWithAddress>|,
             handler: people::register_user
         }
+        update_profile: {
+            inputs: |name: String, avatar_url: String, handle: Option<String>, details: Option<people::ProfileDetails>|,
+            outputs: |result: ZomeApiResult<people::PersonWithAddress>|,
+            handler: people::update_profile
+        }
+        get_history: {
+            inputs: |agent_id: Address|,
+            outputs: |result: ZomeApiResult<Vec<people::PersonWithAddress>>|,
+            handler: people::get_history
+        }
+        get_by_handle: {
+            inputs: |handle: String|,
+            outputs: |result: ZomeApiResult<people::PersonWithAddress>|,
+            handler: handle::get_by_handle
+        }
+        is_handle_available: {
+            inputs: |handle: String|,
+            outputs: |result: ZomeApiResult<bool>|,
+            handler: handle::is_handle_available
+        }
         all: {
             inputs: | |,
             outputs: |result: ZomeApiResult<Vec<people::PersonWithAddress>>|,
             handler: people::all
         }
+        directory: {
+            inputs: |search: Option<String>, after: Option<Address>, limit: Option<usize>|,
+            outputs: |result: ZomeApiResult<directory::DirectoryResult>|,
+            handler: directory::directory
+        }
+        community_members: {
+            inputs: |community_address: Address, role: Option<Role>, active_days: Option<u32>, now: Option<String>, after: Option<Address>, limit: Option<usize>|,
+            outputs: |result: ZomeApiResult<members::CommunityMembersResult>|,
+            handler: members::community_members
+        }
+        resolve_mentions: {
+            inputs: |names: Vec<String>|,
+            outputs: |result: ZomeApiResult<Vec<Address>>|,
+            handler: mentions::resolve_mentions
+        }
+        my_mentions: {
+            inputs: |since: Option<String>, limit: Option<usize>|,
+            outputs: |result: ZomeApiResult<Vec<mentions::Mention>>|,
+            handler: mentions::my_mentions
+        }
     ]
     traits: {
         hc_public [
@@ -72,7 +119,15 @@ define_zome! {
             get_me,
             is_registered,
             register_user,
-            all
+            update_profile,
+            get_history,
+            get_by_handle,
+            is_handle_available,
+            directory,
+            community_members,
+            all,
+            resolve_mentions,
+            my_mentions
         ]
     }
 }
@@ -0,0 +1,93 @@
+use hdk::{
+    error::{ZomeApiError, ZomeApiResult},
+    holochain_json_api::{error::JsonError, json::JsonString},
+    holochain_persistence_api::cas::content::Address,
+};
+use hdk_helpers::{
+    call_zome,
+    communities::{CommunityStats, Member, Role},
+};
+
+use crate::directory::DEFAULT_PAGE_SIZE;
+use crate::people::{self, PersonWithAddress};
+
+#[derive(Serialize, Deserialize, Debug, Clone, DefaultJson)]
+pub struct MemberWithProfile {
+    pub agent_id: Address,
+    pub role: Role,
+    // left out for members who haven't registered a profile
+    #[serde(flatten)]
+    pub person: Option<PersonWithAddress>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, DefaultJson)]
+pub struct CommunityMembersResult {
+    members: Vec<MemberWithProfile>,
+    more: bool,
+}
+
+/**
+ * @brief      List the members of a community with their profiles, one page at a time, sorted by agent address
+ *
+ * @param      community_address   Address of the community
+ *
+ * @param      role                Only include members whose highest role is this
+ *
+ * @param      active_days         Only include members who posted in the community in this many days before now
+ *
+ * @param      now                 The current time as an RFC3339 timestamp. Needed with active_days
+ *
+ * @param      after               Agent address of the last member of the previous page. Omit to get the first page
+ *
+ * @param      limit               Page size
+ *
+ * @return     The page of members and whether there are more after it. Members without a profile are
+ *             included without one
+ */
+pub fn community_members(
+    community_address: Address,
+    role: Option<Role>,
+    active_days: Option<u32>,
+    now: Option<String>,
+    after: Option<Address>,
+    limit: Option<usize>,
+) -> ZomeApiResult<CommunityMembersResult> {
+    let limit = limit.unwrap_or(DEFAULT_PAGE_SIZE);
+    // one per agent, with their highest role
+    let mut members: Vec<Member> = call_zome(
+        "communities",
+        "members",
+        json!({ "community_address": community_address, "role": role }),
+    )?;
+
+    if let Some(days) = active_days {
+        let now = now.ok_or_else(|| {
+            ZomeApiError::Internal("now is needed to filter by recent activity".into())
+        })?;
+        let stats: CommunityStats = call_zome(
+            "communities",
+            "stats",
+            json!({ "community": community_address, "now": now, "days": days }),
+        )?;
+        members.retain(|member| stats.active_posters.contains(&member.agent_id));
+    }
+
+    // the member links give the order, so only the profiles on the page are loaded
+    members.sort_by(|a, b| a.agent_id.to_string().cmp(&b.agent_id.to_string()));
+    if let Some(after) = after {
+        let after = after.to_string();
+        members.retain(|member| member.agent_id.to_string() > after);
+    }
+    let more = members.len() > limit;
+    let members = members
+        .into_iter()
+        .take(limit)
+        .map(|member| MemberWithProfile {
+            person: people::get(member.agent_id.clone()).ok(),
+            agent_id: member.agent_id,
+            role: member.role,
+        })
+        .collect();
+
+    Ok(CommunityMembersResult { members, more })
+}
@@ -0,0 +1,124 @@
+use hdk::{
+    self,
+    error::{ZomeApiError, ZomeApiResult},
+    holochain_core_types::{link::LinkMatch, time::Iso8601},
+    holochain_json_api::{error::JsonError, json::JsonString},
+    holochain_persistence_api::cas::content::Address,
+    AGENT_ADDRESS,
+};
+use hdk_helpers::MENTION_LINK_TYPE;
+use std::convert::TryFrom;
+
+use crate::directory::agents_with_key;
+use crate::handle::{self, normalize_handle, validate_handle};
+
+#[derive(Serialize, Deserialize, Debug, Clone, DefaultJson)]
+pub struct Mention {
+    // address of the post, comment or message
+    pub address: Address,
+    // which of those it is
+    pub kind: String,
+    pub timestamp: String,
+}
+
+// names are matched ignoring case and spaces, so "@JaneDoe" mentions "Jane Doe"
+pub fn normalize(name: &str) -> String {
+    name.split_whitespace().collect::<String>().to_lowercase()
+}
+
+/**
+ * @brief      Find the registered agents mentioned by some @names. Names are looked up in the directory
+ *             listings, so no profiles are loaded
+ *
+ * @param      names       The names without the leading @
+ *
+ * @return     The addresses of the mentioned agents. A name is a handle if an agent owns it. Otherwise it
+ *             mentions the agent with that name, and nobody if several agents share it
+ */
+pub fn resolve_mentions(names: Vec<String>) -> ZomeApiResult<Vec<Address>> {
+    let mut mentioned = Vec::new();
+    for name in &names {
+        let handle = normalize_handle(name);
+        let owner = if validate_handle(&handle).is_ok() {
+            handle::owner_of(&handle)?
+        } else {
+            None
+        };
+        let agent_id = match owner {
+            Some(agent_id) => Some(agent_id),
+            None => {
+                let key = normalize(name);
+                if key.is_empty() {
+                    None
+                } else {
+                    let mut agents = agents_with_key(&key)?;
+                    agents.sort_by(|a, b| a.to_string().cmp(&b.to_string()));
+                    agents.dedup();
+                    match agents.len() {
+                        1 => agents.pop(),
+                        _ => None,
+                    }
+                }
+            }
+        };
+        if let Some(agent_id) = agent_id {
+            if !mentioned.contains(&agent_id) {
+                mentioned.push(agent_id);
+            }
+        }
+    }
+    Ok(mentioned)
+}
+
+/**
+ * @brief      List the content the calling agent has been mentioned in, newest first
+ *
+ * @param      since       Only include mentions with a timestamp after this RFC3339 timestamp
+ *
+ * @param      limit       Maximum number of mentions to return
+ *
+ * @return     The mentions. Ones whose timestamp can't be read come last, and are left out when `since` is given
+ */
+pub fn my_mentions(since: Option<String>, limit: Option<usize>) -> ZomeApiResult<Vec<Mention>> {
+    let since = since
+        .map(|since| {
+            Iso8601::try_from(since.as_str())
+                .map_err(|_| ZomeApiError::Internal(format!("Not a valid timestamp: {}", since)))
+        })
+        .transpose()?;
+    // timestamps are compared parsed, as the same time can be written with different offsets
+    let mut mentions: Vec<(Option<Iso8601>, Mention)> = hdk::get_links(
+        &AGENT_ADDRESS,
+        LinkMatch::Exactly(MENTION_LINK_TYPE),
+        LinkMatch::Any,
+    )?
+    .links()
+    .into_iter()
+    .filter_map(|link| {
+        let mut parts = link.tag.splitn(2, ':');
+        match (parts.next(), parts.next()) {
+            (Some(kind), Some(timestamp)) => Some((
+                Iso8601::try_from(timestamp).ok(),
+                Mention {
+                    address: link.address,
+                    kind: kind.to_string(),
+                    timestamp: timestamp.to_string(),
+                },
+            )),
+            _ => None,
+        }
+    })
+    .filter(|(time, _)| {
+        since.as_ref().map_or(true, |since| {
+            time.as_ref().map_or(false, |time| time > since)
+        })
+    })
+    .collect();
+    mentions.sort_by(|(a_time, a), (b_time, b)| {
+        b_time
+            .cmp(a_time)
+            .then_with(|| a.address.to_string().cmp(&b.address.to_string()))
+    });
+    mentions.truncate(limit.unwrap_or(mentions.len()));
+    Ok(mentions.into_iter().map(|(_, mention)| mention).collect())
+}
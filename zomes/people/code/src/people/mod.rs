@@ -1,22 +1,127 @@
 use crate::anchor::{Anchor, ANCHOR_ENTRY_TYPE, ANCHOR_PERSON_LINK_TYPE};
+use crate::directory::{self, registered_agents};
+use crate::handle::{self, normalize_handle, validate_claimed, validate_handle};
 use hdk::{
     self,
     entry_definition::ValidatingEntryType,
     error::{ZomeApiError, ZomeApiResult},
-    holochain_core_types::{dna::entry_types::Sharing, entry::Entry, link::LinkMatch},
+    holochain_core_types::{
+        dna::entry_types::Sharing, entry::Entry, link::LinkMatch, validation::EntryValidationData,
+    },
     holochain_json_api::{error::JsonError, json::JsonString},
     holochain_persistence_api::cas::content::{Address, AddressableContent},
-    utils, AGENT_ADDRESS,
+    utils, LinkValidationData, AGENT_ADDRESS,
 };
-use hdk_helpers::commit_if_not_in_chain;
+use hdk_helpers::{commit_if_not_in_chain, is_valid_url};
+use std::convert::TryFrom;
 
 pub const PERSON_ENTRY_TYPE: &str = "person";
 pub const PERSON_AGENT_LINK_TYPE: &str = "person_to_agent_link";
 
+const MAX_BIO_LENGTH: usize = 1000;
+const MAX_LOCATION_LENGTH: usize = 100;
+const MAX_LABEL_LENGTH: usize = 50;
+const MAX_SOCIAL_LINKS: usize = 10;
+const MAX_TAGS: usize = 30;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SocialLink {
+    // e.g. "twitter"
+    pub name: String,
+    pub url: String,
+}
+
+// The optional parts of a profile. Empty fields are left out of the entry, so profiles from before
+// these existed still read and keep their addresses
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct ProfileDetails {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bio: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub location: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub website: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub social_links: Vec<SocialLink>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub skills: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub interests: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub banner_url: Option<String>,
+}
+
+fn check_length(field: &str, value: &str, max: usize) -> Result<(), String> {
+    if value.chars().count() > max {
+        Err(format!("{} can be at most {} characters", field, max))
+    } else {
+        Ok(())
+    }
+}
+
+fn check_url(field: &str, url: &str) -> Result<(), String> {
+    if is_valid_url(url) {
+        Ok(())
+    } else {
+        Err(format!("{} must be an http or https URL", field))
+    }
+}
+
+fn check_tags(field: &str, tags: &[String]) -> Result<(), String> {
+    if tags.len() > MAX_TAGS {
+        return Err(format!("A profile can have at most {} {}", MAX_TAGS, field));
+    }
+    for tag in tags {
+        if tag.trim().is_empty() {
+            return Err(format!("{} cannot be blank", field));
+        }
+        check_length(field, tag, MAX_LABEL_LENGTH)?;
+    }
+    Ok(())
+}
+
+impl ProfileDetails {
+    pub fn validate(&self) -> Result<(), String> {
+        if let Some(bio) = &self.bio {
+            check_length("bio", bio, MAX_BIO_LENGTH)?;
+        }
+        if let Some(location) = &self.location {
+            check_length("location", location, MAX_LOCATION_LENGTH)?;
+        }
+        if let Some(website) = &self.website {
+            check_url("website", website)?;
+        }
+        if let Some(banner_url) = &self.banner_url {
+            check_url("banner_url", banner_url)?;
+        }
+        if self.social_links.len() > MAX_SOCIAL_LINKS {
+            return Err(format!(
+                "A profile can have at most {} social links",
+                MAX_SOCIAL_LINKS
+            ));
+        }
+        for link in &self.social_links {
+            check_length("social link names", &link.name, MAX_LABEL_LENGTH)?;
+            check_url("social links", &link.url)?;
+        }
+        check_tags("skills", &self.skills)?;
+        check_tags("interests", &self.interests)
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone, DefaultJson)]
 pub struct Person {
     pub name: String,
     pub avatar_url: String,
+    // the agent this is the profile of. Profiles made before updates were possible don't have it,
+    // so two agents with the same details could share one of those entries. New profiles must have it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub agent_id: Option<Address>,
+    // unique, claimed through a handle anchor. See the handle module
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub handle: Option<String>,
+    #[serde(flatten)]
+    pub details: ProfileDetails,
 }
 
 impl Person {
@@ -25,6 +130,8 @@ impl Person {
             address,
             name: self.name.clone(),
             avatar_url: self.avatar_url.clone(),
+            handle: self.handle.clone(),
+            details: self.details.clone(),
         }
     }
 }
@@ -34,37 +141,110 @@ pub struct PersonWithAddress {
     pub address: Address,
     pub name: String,
     pub avatar_url: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub handle: Option<String>,
+    #[serde(flatten)]
+    pub details: ProfileDetails,
 }
 
-pub fn get(agent_id: Address) -> ZomeApiResult<PersonWithAddress> {
-    let person = utils::get_links_and_load_type::<Person>(
-        &agent_id,
+fn not_registered() -> ZomeApiError {
+    ZomeApiError::Internal("Agent has not been registered".into())
+}
+
+// the entries linked from an agent as their profile. Normally one, but registering twice
+// before updates were possible linked more
+fn person_addresses(agent_id: &Address) -> ZomeApiResult<Vec<Address>> {
+    Ok(hdk::get_links(
+        agent_id,
         LinkMatch::Exactly(PERSON_AGENT_LINK_TYPE.into()),
         LinkMatch::Any,
     )?
-    .first()
-    .map(|result| result.to_owned());
-
-    match person {
-        Some(person) => Ok(person.with_address(agent_id)),
-        None => Err(ZomeApiError::Internal(
-            "Agent has not been registered".into(),
-        )),
+    .addresses())
+}
+
+fn latest(agent_id: &Address) -> ZomeApiResult<Person> {
+    let address = person_addresses(agent_id)?
+        .into_iter()
+        .next()
+        .ok_or_else(not_registered)?;
+    utils::get_as_type::<Person>(address)
+}
+
+// A profile as it is shown. Claims are never deleted, so a profile can still name a handle its agent
+// released or lost to an earlier claim. The handle is only shown while the agent owns it
+fn shown(person: &Person, agent_id: Address) -> ZomeApiResult<PersonWithAddress> {
+    let mut shown = person.with_address(agent_id);
+    if let Some(handle) = &shown.handle {
+        if handle::owner_of(handle)?.as_ref() != Some(&shown.address) {
+            shown.handle = None;
+        }
+    }
+    Ok(shown)
+}
+
+// The latest version of an agent's profile. Reading your own also lists it in the directory if it
+// isn't yet. Only the agent can list themselves, so other readers leave it as it is
+pub fn get(agent_id: Address) -> ZomeApiResult<PersonWithAddress> {
+    let person = latest(&agent_id)?;
+    if agent_id.to_string() == AGENT_ADDRESS.to_string() {
+        // a read shouldn't fail because listing did, it is tried again on the next one
+        let _ = directory::ensure_indexed(&person);
     }
+    shown(&person, agent_id)
+}
+
+// Every version of an agent's profile, oldest first
+pub fn get_history(agent_id: Address) -> ZomeApiResult<Vec<PersonWithAddress>> {
+    let address = person_addresses(&agent_id)?
+        .into_iter()
+        .next()
+        .ok_or_else(not_registered)?;
+    let history = hdk::get_entry_history(&address)?.ok_or_else(not_registered)?;
+    history
+        .items
+        .into_iter()
+        .filter_map(|item| match item.entry_with_meta.entry {
+            Entry::App(_, content) => Person::try_from(content).ok(),
+            _ => None,
+        })
+        .map(|person| shown(&person, agent_id.clone()))
+        .collect()
 }
 
+// This agent's profile. Lists it in the directory if it isn't yet
 pub fn get_me() -> ZomeApiResult<PersonWithAddress> {
-    get(AGENT_ADDRESS.to_string().into())
+    let agent_id = Address::from(AGENT_ADDRESS.to_string());
+    let person = latest(&agent_id)?;
+    directory::ensure_indexed(&person)?;
+    shown(&person, agent_id)
 }
 
 pub fn is_registered() -> ZomeApiResult<bool> {
     Ok(get(AGENT_ADDRESS.to_string().into()).is_ok())
 }
 
-pub fn register_user(name: String, avatar_url: String) -> ZomeApiResult<PersonWithAddress> {
+// Create this agent's profile, or update it if they already have one
+pub fn register_user(
+    name: String,
+    avatar_url: String,
+    handle: Option<String>,
+    details: Option<ProfileDetails>,
+) -> ZomeApiResult<PersonWithAddress> {
+    if is_registered()? {
+        return update_profile(name, avatar_url, handle, details);
+    }
+    let details = details.unwrap_or_default();
+    details.validate().map_err(ZomeApiError::Internal)?;
+    let handle = handle.map(|handle| normalize_handle(&handle));
+    if let Some(handle) = &handle {
+        handle::claim(handle)?;
+    }
     let person = Person {
         name: name.clone(),
         avatar_url: avatar_url.clone(),
+        agent_id: Some(AGENT_ADDRESS.to_string().into()),
+        handle,
+        details,
     };
 
     let person_entry = Entry::App(PERSON_ENTRY_TYPE.into(), person.clone().into());
@@ -81,27 +261,90 @@ pub fn register_user(name: String, avatar_url: String) -> ZomeApiResult<PersonWi
     );
     let anchor_addr = commit_if_not_in_chain(&anchor_entry)?;
     hdk::link_entries(&anchor_addr, &AGENT_ADDRESS, ANCHOR_PERSON_LINK_TYPE, "")?;
+    directory::index(&person)?;
 
     Ok(person.with_address(AGENT_ADDRESS.to_string().into()))
 }
 
-pub fn all() -> ZomeApiResult<Vec<PersonWithAddress>> {
-    let anchor_entry = Entry::App(
-        ANCHOR_ENTRY_TYPE.into(),
-        Anchor {
-            name: "people".into(),
+/**
+ * @brief      Change this agent's profile. Earlier versions stay available through get_history
+ *
+ * @param      handle      A new handle. The old one is given up once the new one is claimed.
+ *                         Leave out to keep the current handle
+ *
+ * @param      details     Bio, location, links, skills etc. replacing the current ones.
+ *                         Leave out to keep the current details
+ *
+ * @return     The updated profile
+ */
+pub fn update_profile(
+    name: String,
+    avatar_url: String,
+    handle: Option<String>,
+    details: Option<ProfileDetails>,
+) -> ZomeApiResult<PersonWithAddress> {
+    let agent_id = Address::from(AGENT_ADDRESS.to_string());
+    let addresses = person_addresses(&agent_id)?;
+    let address = addresses.first().cloned().ok_or_else(not_registered)?;
+    let current = utils::get_as_type::<Person>(address.clone())?;
+    let details = details.unwrap_or_else(|| current.details.clone());
+    details.validate().map_err(ZomeApiError::Internal)?;
+    let handle = match handle.map(|handle| normalize_handle(&handle)) {
+        Some(handle) => {
+            if current.handle.as_ref() != Some(&handle) {
+                handle::claim(&handle)?;
+                if let Some(old_handle) = &current.handle {
+                    handle::release(old_handle)?;
+                }
+            }
+            Some(handle)
         }
-        .into(),
-    );
-    Ok(hdk::get_links(
-        &anchor_entry.address(),
-        LinkMatch::Exactly(ANCHOR_PERSON_LINK_TYPE.into()),
-        LinkMatch::Any,
-    )?
-    .addresses()
-    .iter()
-    .map(|address| get(address.to_string().into()).unwrap())
-    .collect())
+        None => current.handle.clone(),
+    };
+    let updated = Person {
+        name,
+        avatar_url,
+        agent_id: Some(agent_id.clone()),
+        handle,
+        details,
+    };
+    let entry = Entry::App(PERSON_ENTRY_TYPE.into(), updated.clone().into());
+
+    if current.agent_id.is_some() {
+        let latest = hdk::get_entry(&address)?.ok_or_else(not_registered)?;
+        hdk::update_entry(entry, &latest.address())?;
+    } else {
+        // an old profile may be shared with other agents, so start a new one rather than changing it.
+        // The new one is linked first so the agent has a profile even if unlinking the old ones fails
+        let new_address = hdk::commit_entry(&entry)?;
+        hdk::link_entries(&agent_id, &new_address, PERSON_AGENT_LINK_TYPE, "")?;
+        let failed: Vec<String> = addresses
+            .iter()
+            .filter(|old_address| {
+                hdk::remove_link(&agent_id, old_address, PERSON_AGENT_LINK_TYPE, "").is_err()
+            })
+            .map(|old_address| old_address.to_string())
+            .collect();
+        if !failed.is_empty() {
+            return Err(ZomeApiError::Internal(format!(
+                "Could not unlink the old profiles {}. Updating again will retry",
+                failed.join(", ")
+            )));
+        }
+    }
+    if current.name != updated.name || current.handle != updated.handle {
+        directory::unindex(&current)?;
+        directory::index(&updated)?;
+    }
+    Ok(updated.with_address(agent_id))
+}
+
+// Everyone with a profile. Agents linked from the people anchor without one are left out
+pub fn all() -> ZomeApiResult<Vec<PersonWithAddress>> {
+    Ok(registered_agents()?
+        .into_iter()
+        .filter_map(|agent_id| get(agent_id).ok())
+        .collect())
 }
 
 pub fn def() -> ValidatingEntryType {
@@ -114,8 +357,40 @@ pub fn def() -> ValidatingEntryType {
             hdk::ValidationPackageDefinition::Entry
         },
 
-        validation: |_validation_data: hdk::EntryValidationData<Person>| {
-            Ok(())
+        validation: |validation_data: hdk::EntryValidationData<Person>| {
+            match validation_data {
+                EntryValidationData::Create{ entry, validation_data } => {
+                    entry.details.validate()?;
+                    let agent_id = match entry.agent_id {
+                        Some(agent_id) if validation_data.sources().contains(&agent_id) => agent_id,
+                        _ => return Err("Agents can only create their own profile".into()),
+                    };
+                    match &entry.handle {
+                        Some(handle) => {
+                            validate_handle(handle)?;
+                            validate_claimed(handle, &agent_id)
+                        },
+                        None => Ok(()),
+                    }
+                },
+                EntryValidationData::Modify{ new_entry, old_entry, validation_data, .. } => {
+                    new_entry.details.validate()?;
+                    let agent_id = match old_entry.agent_id {
+                        Some(agent_id) if validation_data.sources().contains(&agent_id)
+                            && new_entry.agent_id == Some(agent_id.clone()) => agent_id,
+                        _ => return Err("Agents can only update their own profile".into()),
+                    };
+                    // a handle kept from the old version was checked when it was set
+                    match &new_entry.handle {
+                        Some(handle) if new_entry.handle != old_entry.handle => {
+                            validate_handle(handle)?;
+                            validate_claimed(handle, &agent_id)
+                        },
+                        _ => Ok(()),
+                    }
+                },
+                EntryValidationData::Delete{ .. } => Err("Profiles cannot be deleted".into()),
+            }
         },
 
         links: [
@@ -127,8 +402,17 @@ pub fn def() -> ValidatingEntryType {
                     hdk::ValidationPackageDefinition::Entry
                 },
 
-                validation: |_validation_data: hdk::LinkValidationData| {
-                    Ok(())
+                // only the agent can set their profile
+                validation: |validation_data: hdk::LinkValidationData| {
+                    let (link, validation_data) = match validation_data {
+                        LinkValidationData::LinkAdd { link, validation_data } => (link, validation_data),
+                        LinkValidationData::LinkRemove { link, validation_data } => (link, validation_data),
+                    };
+                    if validation_data.sources().contains(link.link().base()) {
+                        Ok(())
+                    } else {
+                        Err("Only the agent can change which profile is theirs".into())
+                    }
                 }
             )
         ]
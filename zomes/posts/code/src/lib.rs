@@ -6,6 +6,7 @@ extern crate serde_derive;
 extern crate serde_json;
 #[macro_use]
 extern crate holochain_json_derive;
+extern crate chrono;
 
 use hdk::{
     error::ZomeApiResult,
@@ -44,7 +45,7 @@ define_zome! {
             handler: post::get
         }
         create: {
-            inputs: |base: String, title: String, details: String, post_type: String, announcement: bool, timestamp: String|,
+            inputs: |base: String, title: String, details: String, post_type: String, announcement: bool, timestamp: String, attachments: Option<Vec<Address>>|,
             outputs: |result: ZomeApiResult<post::PostWithAddress>|,
             handler: post::create
         }
@@ -53,6 +54,16 @@ define_zome! {
             outputs: |result: ZomeApiResult<post::GetPostsResult>|,
             handler: post::all_for_base
         }
+        activity: {
+            inputs: |bases: Vec<String>, since: Option<String>|,
+            outputs: |result: ZomeApiResult<post::PostActivity>|,
+            handler: post::activity
+        }
+        index_base: {
+            inputs: |base: String|,
+            outputs: |result: ZomeApiResult<usize>|,
+            handler: post::index_base
+        }
         adjacency_list_for_base: {
             inputs: |base: String, since: Option<Address>|,
             outputs: |result: ZomeApiResult<Vec<(Address, Address)>>|,
@@ -65,6 +76,8 @@ define_zome! {
             get,
             create,
             all_for_base,
+            activity,
+            index_base,
             adjacency_list_for_base
         ]
     }
@@ -1,17 +1,26 @@
 use hdk::{
     self,
     entry_definition::ValidatingEntryType,
-    error::ZomeApiResult,
-    holochain_core_types::{dna::entry_types::Sharing, entry::Entry, link::LinkMatch},
+    error::{ZomeApiError, ZomeApiResult},
+    holochain_core_types::{
+        dna::entry_types::Sharing, entry::Entry, link::LinkMatch, validation::EntryValidationData,
+    },
     holochain_json_api::{
         error::JsonError,
         json::{JsonString, RawString},
     },
     holochain_persistence_api::cas::content::{Address, AddressableContent},
     prelude::{QueryArgsOptions, QueryResult},
-    utils, AGENT_ADDRESS,
+    utils, LinkValidationData, AGENT_ADDRESS,
+};
+use chrono::{DateTime, FixedOffset};
+use hdk_helpers::{
+    communities::{community_status, CommunityStatus},
+    files::{check_attachments, validate_attachments},
+    link_mentions,
+    posts::POST_ENTRY_TYPE,
+    validate_mention_link, DagList, DagListDebug, MENTION_LINK_TYPE,
 };
-use hdk_helpers::{DagList, DagListDebug};
 use std::convert::TryFrom;
 
 #[derive(Serialize, Deserialize, Debug, Clone, DefaultJson)]
@@ -23,6 +32,9 @@ pub struct Post {
     pub announcement: bool,
     pub timestamp: String,
     pub base: String,
+    // addresses of files stored with the files zome
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub attachments: Vec<Address>,
     // fields for the dag list
     prev_authored: Address,
     prev_foreign: Address,
@@ -39,6 +51,7 @@ impl Post {
             announcement: self.announcement.clone(),
             timestamp: self.timestamp.clone(),
             base: self.base.clone(),
+            attachments: self.attachments.clone(),
         }
     }
 }
@@ -53,13 +66,30 @@ pub struct PostWithAddress {
     pub announcement: bool,
     pub timestamp: String,
     pub base: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub attachments: Vec<Address>,
 }
 
 pub type Base = RawString;
 
-const POST_ENTRY_TYPE: &str = "post";
 const POST_BASE_ENTRY: &str = "post_base";
 const POST_LINK_TYPE: &str = "posted_in";
+// base -> creator of a post in the base, one per post. Tagged with the post timestamp and the post address
+// (see poster_tag) so validators can check the creator
+const POSTER_LINK_TYPE: &str = "has_poster";
+
+fn poster_tag(timestamp: &str, post_address: &Address) -> String {
+    format!("{}|{}", timestamp, post_address)
+}
+
+// poster links made before they named the post are tagged with only the timestamp
+fn tag_timestamp(tag: &str) -> &str {
+    tag.split('|').next().unwrap_or(tag)
+}
+
+fn base_address(base: &str) -> Address {
+    Entry::App(POST_BASE_ENTRY.into(), RawString::from(base).into()).address()
+}
 
 #[derive(Serialize, Deserialize, Debug, Clone, DefaultJson)]
 pub struct GetPostsResult {
@@ -67,6 +97,13 @@ pub struct GetPostsResult {
     more: bool,
 }
 
+#[derive(Serialize, Deserialize, Debug, Clone, DefaultJson)]
+pub struct PostActivity {
+    pub post_count: usize,
+    pub recent_post_count: usize,
+    pub active_posters: Vec<Address>,
+}
+
 pub fn get(address: Address) -> ZomeApiResult<PostWithAddress> {
     utils::get_as_type::<Post>(address.clone()).map(|post| post.with_address(address))
 }
@@ -78,7 +115,18 @@ pub fn create(
     post_type: String,
     announcement: bool,
     timestamp: String,
+    attachments: Option<Vec<Address>>,
 ) -> ZomeApiResult<PostWithAddress> {
+    // archived communities stay readable but don't take new posts. This is only checked here, as
+    // validators looking at the post after the community was archived would reject it
+    if community_status(&base)? == Some(CommunityStatus::Archived) {
+        return Err(ZomeApiError::Internal(
+            "This community has been archived".into(),
+        ));
+    }
+    let attachments = attachments.unwrap_or_default();
+    check_attachments(&attachments)?;
+
     let base_entry = Entry::App(POST_BASE_ENTRY.into(), RawString::from(base.clone()).into());
     let base_address = hdk::commit_entry(&base_entry)?;
 
@@ -88,8 +136,9 @@ pub fn create(
         post_type,
         creator: AGENT_ADDRESS.to_string().into(),
         announcement,
-        timestamp,
+        timestamp: timestamp.clone(),
         base: base.clone(),
+        attachments,
         prev_authored: Address::new(), // these will get overwritten
         prev_foreign: Address::new(),
     };
@@ -97,6 +146,17 @@ pub fn create(
     let mut store = PostDagList::new();
     let post_address = store.add_content_dag(&base, post.clone(), &base_address)?;
 
+    // index the post on its base so counts don't need a traversal of the DAG
+    hdk::link_entries(&base_address, &post_address, POST_LINK_TYPE, &timestamp)?;
+    hdk::link_entries(
+        &base_address,
+        &AGENT_ADDRESS,
+        POSTER_LINK_TYPE,
+        &poster_tag(&timestamp, &post_address),
+    )?;
+
+    link_mentions(&post.details, &post_address, POST_ENTRY_TYPE, &timestamp);
+
     Ok(post.with_address(post_address))
 }
 
@@ -138,6 +198,111 @@ pub fn all_for_base(
     Ok(GetPostsResult { posts, more })
 }
 
+fn parse_timestamp(timestamp: &str) -> Option<DateTime<FixedOffset>> {
+    DateTime::parse_from_rfc3339(timestamp).ok()
+}
+
+fn is_since(tag: &str, since: &Option<DateTime<FixedOffset>>) -> bool {
+    match since {
+        Some(since) => parse_timestamp(tag).map_or(false, |time| time >= *since),
+        None => true,
+    }
+}
+
+/**
+ * @brief      Count the posts in some bases using the link index kept up to date by create.
+ *             Posts made before create kept the index are only counted once index_base has been run for their base
+ *
+ * @param      bases       Bases to add up. A community may have posts under more than one base (e.g. slug and address)
+ *
+ * @param      since       RFC3339 timestamp. Posts at or after this count as recent and their creators as active.
+ *                         If omitted every post is recent
+ *
+ * @return     Total and recent post counts and the distinct agents who posted recently
+ */
+pub fn activity(bases: Vec<String>, since: Option<String>) -> ZomeApiResult<PostActivity> {
+    let since = match since {
+        Some(since) => Some(parse_timestamp(&since).ok_or(ZomeApiError::Internal(
+            "since must be an RFC3339 timestamp".into(),
+        ))?),
+        None => None,
+    };
+    let mut post_count = 0;
+    let mut recent_post_count = 0;
+    let mut active_posters = Vec::new();
+    for base in bases {
+        let base_address = base_address(&base);
+        let mut post_links = hdk::get_links(
+            &base_address,
+            LinkMatch::Exactly(POST_LINK_TYPE),
+            LinkMatch::Any,
+        )?
+        .links();
+        // a post indexed twice is still one post
+        post_links.sort_by(|a, b| a.address.to_string().cmp(&b.address.to_string()));
+        post_links.dedup_by(|a, b| a.address == b.address);
+        post_count += post_links.len();
+        recent_post_count += post_links
+            .iter()
+            .filter(|link| is_since(&link.tag, &since))
+            .count();
+        for link in hdk::get_links(
+            &base_address,
+            LinkMatch::Exactly(POSTER_LINK_TYPE),
+            LinkMatch::Any,
+        )?
+        .links()
+        {
+            if is_since(tag_timestamp(&link.tag), &since) && !active_posters.contains(&link.address)
+            {
+                active_posters.push(link.address);
+            }
+        }
+    }
+    Ok(PostActivity {
+        post_count,
+        recent_post_count,
+        active_posters,
+    })
+}
+
+/**
+ * @brief      Add the posts of a base that were made before create kept the link index used by activity.
+ *             This walks the whole DAG of the base, so it is meant to be run once per base, not on every read.
+ *             Anyone can run it as validation only accepts index links that match the post they point at
+ *
+ * @param      base        The base to index
+ *
+ * @return     The number of posts added to the index
+ */
+pub fn index_base(base: String) -> ZomeApiResult<usize> {
+    let base_address = base_address(&base);
+    let indexed = hdk::get_links(
+        &base_address,
+        LinkMatch::Exactly(POST_LINK_TYPE),
+        LinkMatch::Any,
+    )?
+    .addresses();
+    let store = PostDagList::new();
+    let (addresses, _) = store.get_content_dag(&base, &base_address, None, None)?;
+    let mut added = 0;
+    for address in addresses {
+        if indexed.contains(&address) {
+            continue;
+        }
+        let post = utils::get_as_type::<Post>(address.clone())?;
+        hdk::link_entries(&base_address, &address, POST_LINK_TYPE, &post.timestamp)?;
+        hdk::link_entries(
+            &base_address,
+            &post.creator,
+            POSTER_LINK_TYPE,
+            &poster_tag(&post.timestamp, &address),
+        )?;
+        added += 1;
+    }
+    Ok(added)
+}
+
 pub fn adjacency_list_for_base(
     base: String,
     root: Option<Address>,
@@ -259,8 +424,13 @@ pub fn post_def() -> ValidatingEntryType {
             hdk::ValidationPackageDefinition::Entry
         },
 
-        validation: |_validation_data: hdk::EntryValidationData<Post>| {
-            Ok(())
+        validation: |validation_data: hdk::EntryValidationData<Post>| {
+            match validation_data {
+                // whether a community is archived depends on when a validator looks, so that is
+                // only checked by create
+                EntryValidationData::Create{ entry, .. } => validate_attachments(&entry.attachments),
+                _ => Ok(()),
+            }
         },
         links: [
             to!(
@@ -292,11 +462,70 @@ pub fn post_def() -> ValidatingEntryType {
                 validation: | _validation_data: hdk::LinkValidationData| {
                     Ok(())
                 }
+            ),
+            from!(
+                "%agent_id",
+                link_type: MENTION_LINK_TYPE,
+                validation_package: || {
+                    hdk::ValidationPackageDefinition::Entry
+                },
+                validation: | validation_data: hdk::LinkValidationData| {
+                    validate_mention_link(validation_data)
+                }
             )
         ]
     )
 }
 
+// A post link must point at a post in the base it is linked from and be tagged with its timestamp
+fn validate_post_link(validation_data: LinkValidationData) -> Result<(), String> {
+    match validation_data {
+        LinkValidationData::LinkAdd { link, .. } => {
+            let link = link.link();
+            let post = utils::get_as_type::<Post>(link.target().clone())
+                .map_err(|_| String::from("Post links must point to a post"))?;
+            if base_address(&post.base) != *link.base() {
+                return Err("The post is not in this base".into());
+            }
+            if post.timestamp != link.tag().to_string() {
+                return Err("Post links must be tagged with the post timestamp".into());
+            }
+            Ok(())
+        }
+        // the index is never pruned, so a removal could only hide posts from the counts
+        LinkValidationData::LinkRemove { .. } => Err("Post index links cannot be removed".into()),
+    }
+}
+
+// A poster link must name a post in the base it is linked from and point at the creator of that post
+fn validate_poster_link(validation_data: LinkValidationData) -> Result<(), String> {
+    match validation_data {
+        LinkValidationData::LinkAdd { link, .. } => {
+            let link = link.link();
+            let tag = link.tag().to_string();
+            let mut parts = tag.splitn(2, '|');
+            let timestamp = parts.next().unwrap_or_default();
+            let post_address = parts
+                .next()
+                .ok_or_else(|| String::from("Poster links must name the post"))?;
+            let post = utils::get_as_type::<Post>(Address::from(post_address))
+                .map_err(|_| String::from("Poster links must name a post"))?;
+            if base_address(&post.base) != *link.base() {
+                return Err("The post is not in this base".into());
+            }
+            if post.creator != *link.target() {
+                return Err("Poster links must point to the creator of the post".into());
+            }
+            if post.timestamp != timestamp {
+                return Err("Poster links must be tagged with the post timestamp".into());
+            }
+            Ok(())
+        }
+        // the index is never pruned, so a removal could only hide posts from the counts
+        LinkValidationData::LinkRemove { .. } => Err("Post index links cannot be removed".into()),
+    }
+}
+
 pub fn base_def() -> ValidatingEntryType {
     entry!(
         name: POST_BASE_ENTRY,
@@ -315,8 +544,18 @@ pub fn base_def() -> ValidatingEntryType {
                 validation_package: || {
                     hdk::ValidationPackageDefinition::Entry
                 },
-                validation: | _validation_data: hdk::LinkValidationData| {
-                    Ok(())
+                validation: | validation_data: hdk::LinkValidationData| {
+                    validate_post_link(validation_data)
+                }
+            ),
+            to!(
+                "%agent_id",
+                link_type: POSTER_LINK_TYPE,
+                validation_package: || {
+                    hdk::ValidationPackageDefinition::Entry
+                },
+                validation: | validation_data: hdk::LinkValidationData| {
+                    validate_poster_link(validation_data)
                 }
             )
         ]